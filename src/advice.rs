@@ -0,0 +1,238 @@
+use std::ops::Range;
+
+use crate::mapping::RawMapping;
+use crate::sys;
+use crate::{GrowableMapping, PrivateMapping};
+
+/// Advice about the expected use of a memory mapping, passed to `madvise`.
+///
+/// See the `madvise(2)` man page for the exact meaning of each value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Advice {
+	/// Expect access in the near future, so the kernel can read ahead.
+	WillNeed,
+
+	/// Do not expect access in the near future.
+	///
+	/// For a shared mapping, the pages are removed from the mapping, but the file contents are not affected.
+	DontNeed,
+
+	/// Enable transparent huge pages for the mapping.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	HugePage,
+
+	/// Populate the page tables of the mapping for reading, without actually reading the memory.
+	///
+	/// Only available on Linux 5.14 and later.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	PopulateRead,
+
+	/// Populate the page tables of the mapping for writing, without actually writing to the memory.
+	///
+	/// This fails for read-only mappings.
+	/// Only available on Linux 5.14 and later.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	PopulateWrite,
+
+	/// Synchronously collapse the memory into transparent huge pages.
+	///
+	/// Only available on Linux 6.1 and later.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	Collapse,
+}
+
+impl Advice {
+	/// Get the raw value for `madvise`.
+	fn to_raw(self) -> std::os::raw::c_int {
+		match self {
+			Self::WillNeed => libc::MADV_WILLNEED,
+			Self::DontNeed => libc::MADV_DONTNEED,
+			#[cfg(any(target_os = "linux", target_os = "android"))]
+			Self::HugePage => libc::MADV_HUGEPAGE,
+			#[cfg(any(target_os = "linux", target_os = "android"))]
+			Self::PopulateRead => sys::flags::MADV_POPULATE_READ,
+			#[cfg(any(target_os = "linux", target_os = "android"))]
+			Self::PopulateWrite => sys::flags::MADV_POPULATE_WRITE,
+			#[cfg(any(target_os = "linux", target_os = "android"))]
+			Self::Collapse => sys::flags::MADV_COLLAPSE,
+		}
+	}
+}
+
+/// A NUMA memory policy for a memory mapping, applied with `mbind`.
+///
+/// NUMA nodes are identified by their number, as listed in `/sys/devices/system/node`.
+/// See the `mbind(2)` man page for the exact meaning of each policy.
+#[cfg(target_os = "linux")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MemoryPolicy<'a> {
+	/// Use the memory policy of the thread that allocates the memory.
+	Default,
+
+	/// Allocate memory on the given node if possible, and fall back to other nodes otherwise.
+	Preferred(usize),
+
+	/// Only allocate memory on the given nodes.
+	Bind(&'a [usize]),
+
+	/// Interleave allocations over the given nodes.
+	Interleave(&'a [usize]),
+
+	/// Allocate memory on the node of the CPU that triggers the allocation.
+	Local,
+}
+
+#[cfg(target_os = "linux")]
+impl MemoryPolicy<'_> {
+	/// Get the raw mode and node mask for `mbind`.
+	fn to_raw(self) -> (std::os::raw::c_int, Vec<libc::c_ulong>) {
+		match self {
+			Self::Default => (sys::flags::MPOL_DEFAULT, Vec::new()),
+			Self::Preferred(node) => (sys::flags::MPOL_PREFERRED, node_mask(&[node])),
+			Self::Bind(nodes) => (sys::flags::MPOL_BIND, node_mask(nodes)),
+			Self::Interleave(nodes) => (sys::flags::MPOL_INTERLEAVE, node_mask(nodes)),
+			Self::Local => (sys::flags::MPOL_LOCAL, Vec::new()),
+		}
+	}
+}
+
+/// Create a node mask for `mbind` from a list of NUMA nodes.
+#[cfg(target_os = "linux")]
+fn node_mask(nodes: &[usize]) -> Vec<libc::c_ulong> {
+	const BITS: usize = libc::c_ulong::BITS as usize;
+	let words = nodes.iter().max().map_or(0, |max| max / BITS + 1);
+	let mut mask = vec![0; words];
+	for &node in nodes {
+		mask[node / BITS] |= 1 << (node % BITS);
+	}
+	mask
+}
+
+impl RawMapping {
+	/// Give advice about the expected use of a range of the mapping.
+	pub fn advise(&self, range: Range<usize>, advice: Advice) -> std::io::Result<()> {
+		let range = self.check_range(range)?;
+		if self.len() == 0 {
+			return Ok(());
+		}
+		sys::madvise(unsafe { self.as_ptr().add(range.start).cast() }, range.len(), advice.to_raw())
+	}
+
+	/// Lock the mapping in RAM.
+	pub fn lock(&self) -> std::io::Result<()> {
+		if self.len() == 0 {
+			return Ok(());
+		}
+		sys::mlock(self.as_ptr().cast(), self.len())
+	}
+
+	/// Unlock the mapping.
+	pub fn unlock(&self) -> std::io::Result<()> {
+		if self.len() == 0 {
+			return Ok(());
+		}
+		sys::munlock(self.as_ptr().cast(), self.len())
+	}
+
+	/// Set the NUMA memory policy of the mapping.
+	#[cfg(target_os = "linux")]
+	pub fn set_memory_policy(&self, policy: MemoryPolicy, migrate: bool) -> std::io::Result<()> {
+		if self.len() == 0 {
+			return Ok(());
+		}
+		let (mode, nodemask) = policy.to_raw();
+		let flags = if migrate { sys::flags::MPOL_MF_MOVE } else { 0 };
+		sys::mbind(self.as_ptr().cast(), self.len(), mode, &nodemask, flags)
+	}
+
+	/// Check that a range is inside the mapping.
+	fn check_range(&self, range: Range<usize>) -> std::io::Result<Range<usize>> {
+		if range.start > range.end || range.end > self.len() {
+			Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "range is outside of the mapping"))
+		} else {
+			Ok(range)
+		}
+	}
+}
+
+impl GrowableMapping {
+	/// Give advice to the kernel about the expected use of the whole mapping, using `madvise`.
+	///
+	/// The advice only applies to the current mapping.
+	/// It may have to be given again after the mapping has been extended by [`Self::refresh()`].
+	pub fn advise(&self, advice: Advice) -> std::io::Result<()> {
+		self.raw().advise(0..self.len(), advice)
+	}
+
+	/// Give advice to the kernel about the expected use of a range of the mapping, using `madvise`.
+	///
+	/// The start of the range must be a multiple of the page size.
+	/// If the range is not fully inside the mapping, an error of kind [`std::io::ErrorKind::InvalidInput`] is returned.
+	pub fn advise_range(&self, range: Range<usize>, advice: Advice) -> std::io::Result<()> {
+		self.raw().advise(range, advice)
+	}
+
+	/// Lock the pages of the mapping in RAM, using `mlock`.
+	///
+	/// The locked memory counts towards the `RLIMIT_MEMLOCK` resource limit of the process.
+	/// The lock only applies to the current mapping.
+	/// If the mapping is extended by [`Self::refresh()`], the new region is not locked until you call this function again.
+	pub fn lock(&self) -> std::io::Result<()> {
+		self.raw().lock()
+	}
+
+	/// Unlock the pages of the mapping, using `munlock`.
+	pub fn unlock(&self) -> std::io::Result<()> {
+		self.raw().unlock()
+	}
+
+	/// Set the NUMA memory policy for the mapping, using `mbind`.
+	///
+	/// If `migrate` is true, the kernel tries to move pages that have already been allocated so they conform to the new policy.
+	/// The policy only applies to the current mapping.
+	/// It may have to be set again after the mapping has been extended by [`Self::refresh()`].
+	#[cfg(target_os = "linux")]
+	pub fn set_memory_policy(&self, policy: MemoryPolicy, migrate: bool) -> std::io::Result<()> {
+		self.raw().set_memory_policy(policy, migrate)
+	}
+}
+
+impl PrivateMapping {
+	/// Give advice to the kernel about the expected use of the whole mapping, using `madvise`.
+	///
+	/// This takes `&mut self` because [`Advice::DontNeed`] discards the private modifications of the mapping,
+	/// so the contents revert to the contents of the file.
+	pub fn advise(&mut self, advice: Advice) -> std::io::Result<()> {
+		self.raw().advise(0..self.len(), advice)
+	}
+
+	/// Give advice to the kernel about the expected use of a range of the mapping, using `madvise`.
+	///
+	/// The start of the range must be a multiple of the page size.
+	/// If the range is not fully inside the mapping, an error of kind [`std::io::ErrorKind::InvalidInput`] is returned.
+	pub fn advise_range(&mut self, range: Range<usize>, advice: Advice) -> std::io::Result<()> {
+		self.raw().advise(range, advice)
+	}
+
+	/// Lock the pages of the mapping in RAM, using `mlock`.
+	///
+	/// The locked memory counts towards the `RLIMIT_MEMLOCK` resource limit of the process.
+	pub fn lock(&self) -> std::io::Result<()> {
+		self.raw().lock()
+	}
+
+	/// Unlock the pages of the mapping, using `munlock`.
+	pub fn unlock(&self) -> std::io::Result<()> {
+		self.raw().unlock()
+	}
+
+	/// Set the NUMA memory policy for the mapping, using `mbind`.
+	///
+	/// If `migrate` is true, the kernel tries to move pages that have already been allocated so they conform to the new policy.
+	#[cfg(target_os = "linux")]
+	pub fn set_memory_policy(&self, policy: MemoryPolicy, migrate: bool) -> std::io::Result<()> {
+		self.raw().set_memory_policy(policy, migrate)
+	}
+}
@@ -0,0 +1,271 @@
+use std::alloc::Layout;
+use std::marker::PhantomData;
+use std::os::unix::fs::FileExt;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::mapping::RawMapping;
+use crate::{sys, CreateOptions, Error, MemFile, Seal};
+
+/// The magic value at the start of an arena file.
+const MAGIC: [u8; 8] = *b"MEMARENA";
+
+/// The offset of the allocation pointer in the header.
+const NEXT_OFFSET: usize = 8;
+
+/// The offset of the first allocation, after the header.
+const DATA_START: u64 = 64;
+
+/// A memory arena in a [`MemFile`] that can be shared with other processes.
+///
+/// The arena is a bump allocator: allocations are carved out of the file one after another,
+/// and memory is only reclaimed when the most recent allocation is freed.
+/// The allocation state is stored in the file itself and updated atomically,
+/// so processes that [attach][Self::attach] to the same file can allocate from it concurrently.
+///
+/// Because other processes map the file at a different address, pointers into the arena are meaningless to them.
+/// Use [`ShmPtr`] to refer to allocations by their offset in the file instead.
+///
+/// With the `allocator-api2` feature, the arena implements the `allocator_api2::alloc::Allocator` trait,
+/// so collections like `allocator_api2::vec::Vec` can be placed in the arena.
+/// Note that those collections still contain absolute pointers, so they can only be used in the process that created them.
+///
+/// The file has a fixed size and is sealed with [`Seal::Shrink`] and [`Seal::Grow`],
+/// so the mapping stays valid in every process and allocations never move.
+#[derive(Debug)]
+pub struct MemFileArena {
+	file: MemFile,
+	mapping: RawMapping,
+}
+
+impl MemFileArena {
+	/// Create a new arena with the given size in bytes.
+	///
+	/// The first 64 bytes of the file are used for the header of the arena,
+	/// so the space available for allocations is slightly smaller than `size`.
+	pub fn create(name: &str, size: u64) -> Result<Self, Error> {
+		if size < DATA_START {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "arena size is too small for the header").into());
+		}
+		let file = MemFile::create(name, CreateOptions::new().allow_sealing(true))?;
+		file.set_len(size)?;
+		file.write_all_at(&MAGIC, 0)?;
+		file.write_all_at(&DATA_START.to_ne_bytes(), NEXT_OFFSET as u64)?;
+		file.add_seals(Seal::Shrink | Seal::Grow)?;
+		Self::attach(file)
+	}
+
+	/// Attach to an existing arena, for example after receiving the file from another process.
+	///
+	/// This function fails with [`Error::MissingSeals`] if the file is not sealed with [`Seal::Shrink`] and [`Seal::Grow`].
+	/// It fails with an error of kind [`std::io::ErrorKind::InvalidData`] if the file does not contain a valid arena header,
+	/// or if the allocation pointer in the header is outside of the file.
+	pub fn attach(file: MemFile) -> Result<Self, Error> {
+		file.require_seals(Seal::Shrink | Seal::Grow)?;
+		let len = usize::try_from(file.metadata()?.len())
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "file is too large to map in memory"))?;
+		let mut header = [0; 16];
+		if len < DATA_START as usize || file.read_exact_at(&mut header, 0).is_err() || header[..NEXT_OFFSET] != MAGIC {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "file does not contain a valid arena header").into());
+		}
+		let next = u64::from_ne_bytes(header[NEXT_OFFSET..].try_into().unwrap());
+		if next < DATA_START || next > len as u64 {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "allocation pointer of the arena is outside of the file").into());
+		}
+
+		let mapping = RawMapping::new(file.as_fd(), len, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED)?;
+		file.apply_thp_mode(&mapping)?;
+		Ok(Self { file, mapping })
+	}
+
+	/// Get the [`MemFile`] that holds the arena.
+	///
+	/// Send the file to other processes and use [`Self::attach()`] to access the arena there.
+	pub fn file(&self) -> &MemFile {
+		&self.file
+	}
+
+	/// Get the total size of the arena in bytes, including the header.
+	pub fn capacity(&self) -> u64 {
+		self.mapping.len() as u64
+	}
+
+	/// Get the number of bytes that have been allocated, including padding for alignment.
+	pub fn used(&self) -> u64 {
+		self.allocated_end().saturating_sub(DATA_START)
+	}
+
+	/// Allocate memory for the given layout.
+	///
+	/// The alignment of the layout can not exceed the page size,
+	/// since that is the only alignment that is guaranteed to be the same for all mappings of the file.
+	///
+	/// Fails with an error of kind [`std::io::ErrorKind::OutOfMemory`] if there is not enough space left in the arena.
+	pub fn allocate_layout(&self, layout: Layout) -> std::io::Result<NonNull<u8>> {
+		if layout.align() > sys::page_size() {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "alignment exceeds the page size"));
+		}
+		let out_of_memory = || std::io::Error::new(std::io::ErrorKind::OutOfMemory, "not enough space left in the arena");
+		let align = layout.align() as u64;
+		let mut next = self.next().load(Ordering::Relaxed);
+		loop {
+			if next < DATA_START {
+				return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "allocation pointer of the arena points into the header"));
+			}
+			let start = next.checked_add(align - 1).ok_or_else(out_of_memory)? & !(align - 1);
+			let end = start.checked_add(layout.size() as u64).ok_or_else(out_of_memory)?;
+			if end > self.capacity() {
+				return Err(out_of_memory());
+			}
+			match self.next().compare_exchange_weak(next, end, Ordering::AcqRel, Ordering::Relaxed) {
+				Ok(_) => return Ok(unsafe { NonNull::new_unchecked(self.mapping.as_ptr().add(start as usize)) }),
+				Err(actual) => next = actual,
+			}
+		}
+	}
+
+	/// Free memory allocated by [`Self::allocate_layout()`].
+	///
+	/// The memory is only reclaimed if it is the most recent allocation in the arena.
+	///
+	/// # Safety
+	/// The memory must have been allocated from this arena with the same layout,
+	/// and it must not be used anymore by any process.
+	pub unsafe fn deallocate_layout(&self, ptr: NonNull<u8>, layout: Layout) {
+		let start = (ptr.as_ptr() as u64).wrapping_sub(self.mapping.as_ptr() as u64);
+		let end = start.wrapping_add(layout.size() as u64);
+		let _ = self.next().compare_exchange(end, start, Ordering::AcqRel, Ordering::Relaxed);
+	}
+
+	/// Move a value into the arena.
+	///
+	/// The value is never dropped.
+	/// Use [`Self::resolve()`] to get a pointer to the value.
+	pub fn alloc<T>(&self, value: T) -> std::io::Result<ShmPtr<T>> {
+		let ptr = self.allocate_layout(Layout::new::<T>())?.cast::<T>();
+		unsafe { ptr.as_ptr().write(value) };
+		// Do not check the offset against the header, since other processes can overwrite the allocation pointer at any time.
+		let offset = ptr.as_ptr() as usize - self.mapping.as_ptr() as usize;
+		Ok(ShmPtr::from_offset(offset as u64))
+	}
+
+	/// Convert a pointer into the arena to a [`ShmPtr`].
+	///
+	/// Returns `None` if the pointed-to value is not fully inside the arena.
+	pub fn to_shm_ptr<T>(&self, ptr: *const T) -> Option<ShmPtr<T>> {
+		let offset = (ptr as usize).checked_sub(self.mapping.as_ptr() as usize)?;
+		let ptr = ShmPtr::from_offset(offset as u64);
+		self.resolve(ptr)?;
+		Some(ptr)
+	}
+
+	/// Get a pointer to the value referenced by a [`ShmPtr`] in this process.
+	///
+	/// Returns `None` if the value would not be fully inside the allocated part of the arena or if the offset is not properly aligned.
+	/// It is up to you to ensure that the memory contains a valid `T` and that there are no conflicting accesses from other processes.
+	pub fn resolve<T>(&self, ptr: ShmPtr<T>) -> Option<NonNull<T>> {
+		let end = ptr.offset.checked_add(std::mem::size_of::<T>() as u64)?;
+		if ptr.offset < DATA_START || end > self.allocated_end() || ptr.offset % std::mem::align_of::<T>() as u64 != 0 {
+			return None;
+		}
+		NonNull::new(unsafe { self.mapping.as_ptr().add(ptr.offset as usize).cast() })
+	}
+
+	/// Check that `count` values of type `T` at `ptr` are inside the allocated part of the arena and properly aligned.
+	pub(crate) fn check_bounds<T>(&self, ptr: *const T, count: u64) -> std::io::Result<NonNull<T>> {
+		let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidData, "offset points outside of the allocated part of the arena");
+		let offset = (ptr as usize).checked_sub(self.mapping.as_ptr() as usize).ok_or_else(invalid)? as u64;
+		let size = count.checked_mul(std::mem::size_of::<T>() as u64).ok_or_else(invalid)?;
+		let end = offset.checked_add(size).ok_or_else(invalid)?;
+		if offset < DATA_START || end > self.allocated_end() || offset % std::mem::align_of::<T>() as u64 != 0 {
+			return Err(invalid());
+		}
+		Ok(unsafe { NonNull::new_unchecked(ptr.cast_mut()) })
+	}
+
+	/// Get the end of the allocated part of the arena.
+	///
+	/// Other processes can write anything to the header, so the allocation pointer is clamped to the size of the mapping.
+	fn allocated_end(&self) -> u64 {
+		self.next().load(Ordering::Acquire).min(self.capacity())
+	}
+
+	/// Get the allocation pointer in the header.
+	fn next(&self) -> &AtomicU64 {
+		// The mapping is page aligned, so the allocation pointer is properly aligned.
+		unsafe { &*self.mapping.as_ptr().add(NEXT_OFFSET).cast::<AtomicU64>() }
+	}
+}
+
+#[cfg(feature = "allocator-api2")]
+unsafe impl allocator_api2::alloc::Allocator for MemFileArena {
+	fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, allocator_api2::alloc::AllocError> {
+		let ptr = self.allocate_layout(layout).map_err(|_| allocator_api2::alloc::AllocError)?;
+		Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+	}
+
+	unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+		self.deallocate_layout(ptr, layout)
+	}
+}
+
+/// A pointer to a value in a [`MemFileArena`], stored as offset from the start of the file.
+///
+/// Unlike a regular pointer, a [`ShmPtr`] has the same meaning in every process that maps the arena,
+/// so it can be stored in the arena itself or sent to other processes.
+/// Use [`MemFileArena::resolve()`] to turn it into a regular pointer.
+#[repr(transparent)]
+pub struct ShmPtr<T> {
+	offset: u64,
+	_marker: PhantomData<fn() -> T>,
+}
+
+impl<T> ShmPtr<T> {
+	/// Create a [`ShmPtr`] from an offset in the file.
+	pub const fn from_offset(offset: u64) -> Self {
+		Self {
+			offset,
+			_marker: PhantomData,
+		}
+	}
+
+	/// Get the offset of the value in the file.
+	pub const fn offset(self) -> u64 {
+		self.offset
+	}
+
+	/// Cast the pointer to a different type.
+	pub const fn cast<U>(self) -> ShmPtr<U> {
+		ShmPtr::from_offset(self.offset)
+	}
+}
+
+impl<T> Copy for ShmPtr<T> {}
+
+impl<T> Clone for ShmPtr<T> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<T> PartialEq for ShmPtr<T> {
+	fn eq(&self, other: &Self) -> bool {
+		self.offset == other.offset
+	}
+}
+
+impl<T> Eq for ShmPtr<T> {}
+
+impl<T> std::hash::Hash for ShmPtr<T> {
+	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+		self.offset.hash(state)
+	}
+}
+
+impl<T> std::fmt::Debug for ShmPtr<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_tuple("ShmPtr")
+			.field(&self.offset)
+			.finish()
+	}
+}
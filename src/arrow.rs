@@ -0,0 +1,152 @@
+//! Exchange Arrow record batches between processes using sealed [`MemFile`]s.
+//!
+//! Use [`write_stream()`] to write record batches in the Arrow IPC stream format to a new [`MemFile`].
+//! The file is sealed with [`Seal::Write`], [`Seal::Shrink`] and [`Seal::Grow`] once the stream is complete,
+//! so the contents can never change afterwards.
+//!
+//! The receiving process uses a [`MappedStreamReader`] to read the record batches.
+//! The reader checks that the file has all three seals, maps it in memory,
+//! and constructs the arrays directly from the mapping without copying the data.
+//!
+//! This module is only available if the `arrow` feature is enabled.
+//!
+//! # Example
+//! ```
+//! # fn main() -> Result<(), arrow_schema::ArrowError> {
+//! use arrow_array::{Int32Array, RecordBatch};
+//! use arrow_schema::{DataType, Field, Schema};
+//! use memfile::arrow::{write_stream, MappedStreamReader};
+//! use std::sync::Arc;
+//!
+//! let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+//! let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(vec![1, 2, 3]))])?;
+//! let file = write_stream("batches", &schema, [&batch])?;
+//!
+//! // Send the file descriptor to another process, which reads the batches.
+//! let reader = MappedStreamReader::new(&file)?;
+//! let batches = reader.collect::<Result<Vec<_>, _>>()?;
+//! assert_eq!(batches, [batch]);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{Seek, Write};
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_buffer::Buffer;
+use arrow_ipc::reader::StreamDecoder;
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, Schema, SchemaRef};
+
+use crate::mapping::RawMapping;
+use crate::{CreateOptions, Error, MemFile, Seal, Seals};
+
+/// The seals that a file must have before it is mapped by a [`MappedStreamReader`].
+const REQUIRED_SEALS: Seals = Seals::from_bits_truncate(Seal::Write as u32 | Seal::Shrink as u32 | Seal::Grow as u32);
+
+/// Write record batches to a new [`MemFile`] in the Arrow IPC stream format.
+///
+/// After the stream has been written, the file is sealed with [`Seal::Write`], [`Seal::Shrink`] and [`Seal::Grow`].
+/// The file position of the returned [`MemFile`] is at the start of the file.
+///
+/// Errors from the [`MemFile`] are reported as [`ArrowError::ExternalError`] containing an [`Error`].
+pub fn write_stream<'a>(name: &str, schema: &Schema, batches: impl IntoIterator<Item = &'a RecordBatch>) -> Result<MemFile, ArrowError> {
+	let mut file = MemFile::create(name, CreateOptions::new().allow_sealing(true))
+		.map_err(external)?;
+
+	let mut writer = StreamWriter::try_new(std::io::BufWriter::new(&mut file), schema)?;
+	for batch in batches {
+		writer.write(batch)?;
+	}
+	writer.finish()?;
+	writer.into_inner()?.flush()?;
+
+	file.rewind()?;
+	file.add_seals(REQUIRED_SEALS).map_err(external)?;
+	Ok(file)
+}
+
+/// A reader for an Arrow IPC stream in a sealed [`MemFile`], which constructs arrays directly from a memory mapping.
+///
+/// The mapping stays alive for as long as any array that was read from it.
+/// Array data that is not properly aligned in the file is copied instead.
+///
+/// The reader implements [`Iterator`], yielding one [`RecordBatch`] at a time.
+#[derive(Debug)]
+pub struct MappedStreamReader {
+	buffer: Buffer,
+	decoder: StreamDecoder,
+	schema: SchemaRef,
+	pending: Option<RecordBatch>,
+	done: bool,
+}
+
+impl MappedStreamReader {
+	/// Map a file and read the schema of the stream.
+	///
+	/// The file must be sealed with [`Seal::Write`], [`Seal::Shrink`] and [`Seal::Grow`],
+	/// because the arrays borrow the mapped memory and must not change while they are used.
+	/// If some of the seals are missing, the function fails with an [`ArrowError::ExternalError`] containing [`Error::MissingSeals`].
+	pub fn new(file: &MemFile) -> Result<Self, ArrowError> {
+		file.require_seals(REQUIRED_SEALS).map_err(external)?;
+		let len = usize::try_from(file.metadata()?.len())
+			.map_err(|_| ArrowError::MemoryError("file is too large to map in memory".into()))?;
+		let mapping = RawMapping::new(file.as_fd(), len, libc::PROT_READ, libc::MAP_SHARED)?;
+
+		// The file is sealed against writing and resizing, so the mapped memory is valid and immutable for the lifetime of the mapping.
+		let buffer = unsafe {
+			let data = NonNull::new(mapping.as_ptr()).unwrap();
+			Buffer::from_custom_allocation(data, mapping.len(), Arc::new(mapping))
+		};
+
+		let mut reader = Self {
+			buffer,
+			decoder: StreamDecoder::new(),
+			schema: Arc::new(Schema::empty()),
+			pending: None,
+			done: false,
+		};
+		while reader.decoder.schema().is_none() && !reader.buffer.is_empty() {
+			reader.pending = reader.decoder.decode(&mut reader.buffer)?;
+		}
+		reader.schema = reader.decoder.schema()
+			.ok_or_else(|| ArrowError::IpcError("stream does not contain a schema".into()))?;
+		Ok(reader)
+	}
+
+	/// Get the schema of the stream.
+	pub fn schema(&self) -> SchemaRef {
+		self.schema.clone()
+	}
+}
+
+impl Iterator for MappedStreamReader {
+	type Item = Result<RecordBatch, ArrowError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if let Some(batch) = self.pending.take() {
+			return Some(Ok(batch));
+		}
+		if self.done {
+			return None;
+		}
+		match self.decoder.decode(&mut self.buffer) {
+			Ok(Some(batch)) => Some(Ok(batch)),
+			Ok(None) => {
+				self.done = true;
+				self.decoder.finish().err().map(Err)
+			},
+			Err(e) => {
+				self.done = true;
+				Some(Err(e))
+			},
+		}
+	}
+}
+
+/// Wrap an [`Error`] in an [`ArrowError`].
+fn external(error: Error) -> ArrowError {
+	ArrowError::ExternalError(Box::new(error))
+}
@@ -0,0 +1,121 @@
+use std::ffi::CStr;
+use std::fs::File;
+
+use crate::{sys, CreateOptions};
+
+/// The mechanism used to create a [`MemFile`][crate::MemFile].
+///
+/// Normally, all files are created with `memfd_create`.
+/// If that syscall is not available, for example because the kernel is too old or because a sandbox denies it,
+/// a different mechanism can be used to create a memory backed file.
+///
+/// Only files created with `memfd_create` support sealing and huge pages.
+/// Use [`MemFile::backend()`][crate::MemFile::backend] to check which mechanism was used for a file,
+/// or use [`CreateOptions::backend()`] to force a specific mechanism.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Backend {
+	/// The file was created with `memfd_create`.
+	MemfdCreate,
+
+	/// The file was created with `shm_open` and immediately unlinked with `shm_unlink`.
+	///
+	/// This backend is available on Linux and FreeBSD.
+	/// The file is briefly visible to other processes under a random name before it is unlinked.
+	/// If unlinking fails, the name stays visible until the object is removed manually or the system reboots.
+	ShmOpen,
+
+	/// The file was created in `/dev/shm` with the `O_TMPFILE` flag.
+	///
+	/// This backend is only available on Linux, and requires `/dev/shm` to be mounted.
+	TmpFile,
+}
+
+impl Backend {
+	/// Check if files created with this backend support sealing.
+	///
+	/// Note that even with a backend that supports sealing,
+	/// sealing must still be enabled with [`CreateOptions::allow_sealing()`].
+	pub fn supports_sealing(self) -> bool {
+		self == Self::MemfdCreate
+	}
+}
+
+/// The fallback backends to try if `memfd_create` is not available, in order of preference.
+const FALLBACKS: [Backend; 2] = [Backend::TmpFile, Backend::ShmOpen];
+
+/// Create a new file using the backend from the options, or the first backend that works.
+pub(crate) fn create(name: &CStr, options: &CreateOptions) -> std::io::Result<(File, Backend)> {
+	if let Some(backend) = options.backend {
+		let file = create_with(backend, name, options)?;
+		return Ok((file, backend));
+	}
+
+	let error = match sys::memfd_create_cstr(name, options.as_flags()) {
+		Ok(file) => return Ok((file, Backend::MemfdCreate)),
+		Err(e) => e,
+	};
+
+	// Only fall back if the syscall itself is missing or denied,
+	// and never silently drop the request for sealing or huge pages.
+	let missing = matches!(error.raw_os_error(), Some(libc::ENOSYS | libc::EPERM | libc::EACCES));
+	if !missing || options.allow_sealing || options.huge_table.is_some() {
+		return Err(error);
+	}
+
+	for backend in FALLBACKS {
+		if let Ok(file) = create_with(backend, name, options) {
+			return Ok((file, backend));
+		}
+	}
+	Err(error)
+}
+
+/// Create a new file with a specific backend.
+fn create_with(backend: Backend, name: &CStr, options: &CreateOptions) -> std::io::Result<File> {
+	if backend == Backend::MemfdCreate {
+		return sys::memfd_create_cstr(name, options.as_flags());
+	}
+	if options.allow_sealing {
+		return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "sealing is only supported by memfd_create"));
+	}
+	if options.huge_table.is_some() {
+		return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "huge pages are only supported by memfd_create"));
+	}
+	match backend {
+		Backend::MemfdCreate => unreachable!(),
+		Backend::ShmOpen => create_shm_open(),
+		Backend::TmpFile => create_tmpfile(),
+	}
+}
+
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn create_shm_open() -> std::io::Result<File> {
+	use std::sync::atomic::{AtomicU64, Ordering};
+	static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+	// The name is only used until the file is unlinked, but it must not collide with existing objects.
+	loop {
+		let name = format!("/memfile-{}-{}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed));
+		let name = std::ffi::CString::new(name).unwrap();
+		match sys::shm_open_unlinked(&name) {
+			Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+			result => return result,
+		}
+	}
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+fn create_shm_open() -> std::io::Result<File> {
+	Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "shm_open is not supported on this platform"))
+}
+
+#[cfg(target_os = "linux")]
+fn create_tmpfile() -> std::io::Result<File> {
+	sys::open_tmpfile(c"/dev/shm")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn create_tmpfile() -> std::io::Result<File> {
+	Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "O_TMPFILE is not supported on this platform"))
+}
@@ -0,0 +1,181 @@
+//! A process-wide budget for the total size of all [`MemFile`]s.
+//!
+//! Every [`MemFile`] created by this crate is charged for its size, as far as it is known to this crate.
+//! Growing a file with [`MemFile::set_len()`], with writes or with [`MemFile::copy_from_fd()`] charges the budget for the new size,
+//! and shrinking a file with [`MemFile::set_len()`] or dropping the last handle to the file returns the bytes to the budget.
+//! Clones created with [`MemFile::try_clone()`] share the charge of the original file.
+//!
+//! When a limit is set with [`set_limit()`], operations that would grow the total beyond the limit fail with [`Error::BudgetExceeded`],
+//! and creating a new file fails with the same error once the budget has been used up.
+//! No limit is set by default.
+//!
+//! The budget counts the logical size of the files, which is an upper bound for the memory they can use.
+//! Files wrapped with [`MemFile::from_fd()`] are not tracked,
+//! and changes in size made by other processes are only noticed when this process resizes the file.
+//!
+//! Converting a file into a raw file descriptor or a [`File`][std::fs::File], for example with [`MemFile::into_fd()`], [`MemFile::into_file()`],
+//! [`IntoRawFd`][std::os::fd::IntoRawFd] or the [`From`] implementations for [`OwnedFd`][std::os::fd::OwnedFd] and [`Stdio`][std::process::Stdio],
+//! stops the tracking of the file, but its charge stays in the budget forever.
+//! The file can outlive the [`MemFile`], and there is no way to know when it is closed.
+//! Changes in size made through the converted file descriptor are not noticed, unless another clone of the [`MemFile`] resizes the file.
+//!
+//! # Example
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use memfile::{budget, Error, MemFile};
+//!
+//! budget::set_limit(1024 * 1024);
+//! let file = MemFile::create_default("foo")?;
+//! file.set_len(4096)?;
+//! assert!(matches!(file.set_len(2 * 1024 * 1024), Err(Error::BudgetExceeded)));
+//! # budget::set_limit(None);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{Error, MemFile, Seal};
+
+/// The limit of the budget, or [`u64::MAX`] if there is no limit.
+static LIMIT: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// The total number of bytes charged to the budget.
+static USED: AtomicU64 = AtomicU64::new(0);
+
+/// Set the maximum total size of all tracked files in bytes, or remove the limit with `None`.
+///
+/// Lowering the limit below the current usage does not affect existing files,
+/// but they can not grow and no new files can be created until enough bytes have been returned to the budget.
+pub fn set_limit(limit: impl Into<Option<u64>>) {
+	LIMIT.store(limit.into().unwrap_or(u64::MAX), Ordering::Relaxed);
+}
+
+/// Get the maximum total size of all tracked files in bytes, if a limit is set.
+pub fn limit() -> Option<u64> {
+	match LIMIT.load(Ordering::Relaxed) {
+		u64::MAX => None,
+		limit => Some(limit),
+	}
+}
+
+/// Get the total size of all tracked files in bytes.
+pub fn used() -> u64 {
+	USED.load(Ordering::Relaxed)
+}
+
+/// Charge the budget for `bytes` more bytes.
+fn reserve(bytes: u64) -> Result<(), Error> {
+	let limit = LIMIT.load(Ordering::Relaxed);
+	USED.fetch_update(Ordering::AcqRel, Ordering::Relaxed, |used| used.checked_add(bytes).filter(|&used| used <= limit))
+		.map(drop)
+		.map_err(|_| Error::BudgetExceeded)
+}
+
+/// Return `bytes` bytes to the budget.
+fn release(bytes: u64) {
+	USED.fetch_sub(bytes, Ordering::AcqRel);
+}
+
+/// The size limit of a file and the number of bytes it is charged for, shared by all clones of the file.
+#[derive(Debug)]
+pub(crate) struct Accounting {
+	max_size: Option<u64>,
+	charged: AtomicU64,
+}
+
+impl Accounting {
+	/// Start tracking a new file.
+	///
+	/// Fails with [`Error::BudgetExceeded`] if the budget has been used up.
+	pub fn new(max_size: Option<u64>) -> Result<Self, Error> {
+		if let Some(limit) = limit() {
+			if used() >= limit {
+				return Err(Error::BudgetExceeded);
+			}
+		}
+		Ok(Self {
+			max_size,
+			charged: AtomicU64::new(0),
+		})
+	}
+
+	/// Get the maximum size of the file.
+	pub fn max_size(&self) -> Option<u64> {
+		self.max_size
+	}
+
+	/// Charge the budget for growing the file to `size` bytes.
+	///
+	/// Fails with [`Error::SizeLimitExceeded`] if `size` exceeds the maximum size of the file,
+	/// or with [`Error::BudgetExceeded`] if the budget does not have enough room left.
+	pub fn grow_to(&self, size: u64) -> Result<(), Error> {
+		if let Some(max_size) = self.max_size.filter(|&max_size| size > max_size) {
+			return Err(Error::SizeLimitExceeded(max_size));
+		}
+		let mut charged = self.charged.load(Ordering::Relaxed);
+		while size > charged {
+			reserve(size - charged)?;
+			match self.charged.compare_exchange_weak(charged, size, Ordering::AcqRel, Ordering::Relaxed) {
+				Ok(_) => break,
+				Err(actual) => {
+					release(size - charged);
+					charged = actual;
+				},
+			}
+		}
+		Ok(())
+	}
+
+	/// Charge the budget for the actual size of the file, without checking any limits.
+	pub fn update(&self, size: u64) {
+		let charged = self.charged.swap(size, Ordering::AcqRel);
+		if size > charged {
+			USED.fetch_add(size - charged, Ordering::AcqRel);
+		} else {
+			release(charged - size);
+		}
+	}
+}
+
+impl Drop for Accounting {
+	fn drop(&mut self) {
+		release(*self.charged.get_mut());
+	}
+}
+
+impl MemFile {
+	/// Limit a write of `len` bytes to the maximum size of the file, and charge the budget for it.
+	///
+	/// The `offset` function is only called for tracked files, to avoid looking up the file position when it is not needed.
+	/// Returns the number of bytes that may be written.
+	/// Fails with [`Error::SizeLimitExceeded`] if the file has already reached its maximum size.
+	///
+	/// The budget is charged for the whole write up front.
+	/// Call [`Self::update_accounting()`] after the write, also if it failed, to correct the charge for the actual size of the file.
+	pub(crate) fn limit_write(&self, len: usize, offset: impl FnOnce() -> std::io::Result<u64>) -> std::io::Result<usize> {
+		let Some(accounting) = &self.accounting else { return Ok(len) };
+		if len == 0 {
+			return Ok(0);
+		}
+		let offset = offset()?;
+		let len = match accounting.max_size() {
+			Some(max_size) if offset >= max_size => return Err(Error::SizeLimitExceeded(max_size).into()),
+			Some(max_size) => len.min(usize::try_from(max_size - offset).unwrap_or(usize::MAX)),
+			None => len,
+		};
+		accounting.grow_to(offset.saturating_add(len as u64))?;
+		Ok(len)
+	}
+
+	/// Charge the budget for the actual size of the file, and seal the file against growing if it has reached its maximum size.
+	pub(crate) fn update_accounting(&self) {
+		let Some(accounting) = &self.accounting else { return };
+		let Ok(metadata) = self.metadata() else { return };
+		accounting.update(metadata.len());
+		if accounting.max_size().is_some_and(|max_size| metadata.len() >= max_size) {
+			// Files without sealing support are only limited by this crate.
+			let _ = self.add_seal(Seal::Grow);
+		}
+	}
+}
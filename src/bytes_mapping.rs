@@ -0,0 +1,37 @@
+use bytes::Bytes;
+
+use crate::mapping::RawMapping;
+use crate::{Error, MemFile, Seal};
+
+/// A read-only mapping of a sealed file, used as owner of a [`Bytes`] object.
+struct MappedBytes {
+	mapping: RawMapping,
+}
+
+impl AsRef<[u8]> for MappedBytes {
+	fn as_ref(&self) -> &[u8] {
+		// The file is sealed against writing and shrinking, so the mapped data is valid and can not change.
+		unsafe { std::slice::from_raw_parts(self.mapping.as_ptr(), self.mapping.len()) }
+	}
+}
+
+impl MemFile {
+	/// Convert the file into [`Bytes`] backed by a read-only memory mapping.
+	///
+	/// The file must be sealed with [`Seal::Write`] and [`Seal::Shrink`], so the contents of the mapping can never change.
+	/// If some of the seals are missing, this function fails with [`Error::MissingSeals`].
+	///
+	/// The returned [`Bytes`] covers the contents of the file at the time of the call.
+	/// It can be cloned and sliced without copying the data,
+	/// and the mapping is removed when the last clone is dropped.
+	/// The file descriptor is closed when this function returns.
+	///
+	/// This function is only available if the `bytes` feature is enabled.
+	pub fn into_bytes(self) -> Result<Bytes, Error> {
+		self.require_seals(Seal::Write | Seal::Shrink)?;
+		let len = usize::try_from(self.metadata()?.len())
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "file is too large to map in memory"))?;
+		let mapping = RawMapping::new(self.as_fd(), len, libc::PROT_READ, libc::MAP_SHARED)?;
+		Ok(Bytes::from_owner(MappedBytes { mapping }))
+	}
+}
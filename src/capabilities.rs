@@ -0,0 +1,131 @@
+use std::sync::OnceLock;
+
+use crate::sys;
+
+/// The `memfd` features supported by the running kernel.
+///
+/// Which features are available depends on the kernel version and configuration.
+/// Use [`Capabilities::detect()`] to probe the running kernel,
+/// so you can decide up front which strategy to use instead of interpreting errors after the fact.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+	memfd_create: bool,
+	allow_sealing: bool,
+	huge_tlb: bool,
+	future_write: bool,
+	noexec_seal: bool,
+	memfd_secret: bool,
+}
+
+impl Capabilities {
+	/// Detect the capabilities of the running kernel.
+	///
+	/// The kernel is probed by creating throwaway files and trying each flag and seal.
+	/// This is done only once: the result is cached and returned by all subsequent calls.
+	pub fn detect() -> Self {
+		static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+		*CAPABILITIES.get_or_init(Self::probe)
+	}
+
+	/// Probe the running kernel without using the cached result.
+	fn probe() -> Self {
+		use sys::flags::*;
+
+		Self {
+			memfd_create: probe_flags(MFD_CLOEXEC),
+			allow_sealing: probe_flags(MFD_CLOEXEC | MFD_ALLOW_SEALING),
+			huge_tlb: probe_flags(MFD_CLOEXEC | MFD_HUGETLB),
+			future_write: probe_future_write(),
+			noexec_seal: probe_noexec_seal(),
+			memfd_secret: probe_memfd_secret(),
+		}
+	}
+
+	/// Check if `memfd_create` itself is available.
+	///
+	/// This can be `false` on old kernels, or if a sandbox denies the syscall.
+	pub fn memfd_create(&self) -> bool {
+		self.memfd_create
+	}
+
+	/// Check if files can be created with sealing support.
+	///
+	/// See [`CreateOptions::allow_sealing()`][crate::CreateOptions::allow_sealing].
+	pub fn allow_sealing(&self) -> bool {
+		self.allow_sealing
+	}
+
+	/// Check if files can be created in a `hugetlbfs` filesystem.
+	///
+	/// This only checks for the default huge page size.
+	/// Creating a file may still fail for specific page sizes.
+	///
+	/// See [`CreateOptions::huge_tlb()`][crate::CreateOptions::huge_tlb].
+	pub fn huge_tlb(&self) -> bool {
+		self.huge_tlb
+	}
+
+	/// Check if the [`Seal::FutureWrite`][crate::Seal::FutureWrite] seal is supported.
+	///
+	/// This requires Linux 5.1 or later.
+	pub fn future_write(&self) -> bool {
+		self.future_write
+	}
+
+	/// Check if files can be created with the `MFD_NOEXEC_SEAL` flag.
+	///
+	/// This requires Linux 6.3 or later.
+	pub fn noexec_seal(&self) -> bool {
+		self.noexec_seal
+	}
+
+	/// Check if the `memfd_secret` syscall is available.
+	///
+	/// This requires Linux 5.14 or later, and on some kernels it must be enabled explicitly on the kernel command line.
+	pub fn memfd_secret(&self) -> bool {
+		self.memfd_secret
+	}
+}
+
+/// Check if a file can be created with the given flags.
+fn probe_flags(flags: std::os::raw::c_int) -> bool {
+	sys::memfd_create("memfile-probe", flags).is_ok()
+}
+
+#[cfg(target_os = "linux")]
+fn probe_future_write() -> bool {
+	use std::os::fd::AsFd;
+	use sys::flags::*;
+
+	let file = match sys::memfd_create("memfile-probe", MFD_CLOEXEC | MFD_ALLOW_SEALING) {
+		Ok(x) => x,
+		Err(_) => return false,
+	};
+	sys::memfd_add_seals(file.as_fd(), libc::F_SEAL_FUTURE_WRITE).is_ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn probe_future_write() -> bool {
+	false
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn probe_noexec_seal() -> bool {
+	use sys::flags::*;
+	probe_flags(MFD_CLOEXEC | MFD_NOEXEC_SEAL)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn probe_noexec_seal() -> bool {
+	false
+}
+
+#[cfg(target_os = "linux")]
+fn probe_memfd_secret() -> bool {
+	sys::memfd_secret(libc::O_CLOEXEC).is_ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn probe_memfd_secret() -> bool {
+	false
+}
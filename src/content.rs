@@ -0,0 +1,164 @@
+use std::hash::Hasher;
+use std::os::unix::fs::{FileExt, MetadataExt};
+
+use crate::mapping::RawMapping;
+use crate::{sys, Error, MemFile, Seal, Seals};
+
+impl MemFile {
+	/// Feed the contents of the file to a [`Hasher`].
+	///
+	/// The contents are passed to [`Hasher::write()`] in page-sized chunks.
+	/// Only the contents are hashed, not the length of the file.
+	///
+	/// If the file is sealed with [`Seal::Write`] and [`Seal::Shrink`], the contents are read through a read-only memory mapping.
+	/// Otherwise, they are read with positional reads, which do not change the file position.
+	///
+	/// The contents of a file that is not sealed against writes may change while it is being hashed.
+	/// Use [`FrozenDigest`] if you need a digest that is guaranteed to remain valid.
+	pub fn content_hash(&self, hasher: &mut impl Hasher) -> std::io::Result<()> {
+		let contents = Contents::open(self)?;
+		let mut buffer = Vec::new();
+		let mut offset = 0;
+		while offset < contents.len {
+			let chunk = contents.chunk(offset, &mut buffer)?;
+			hasher.write(chunk);
+			offset += chunk.len() as u64;
+		}
+		Ok(())
+	}
+
+	/// Check if this file has exactly the same contents as another file.
+	///
+	/// The files are compared in page-sized chunks, and the comparison stops at the first difference.
+	/// Like [`Self::content_hash()`], files that are sealed with [`Seal::Write`] and [`Seal::Shrink`] are read through a memory mapping.
+	///
+	/// If the contents of either file change during the comparison, the result is unreliable.
+	/// If a file is truncated during the comparison, an error of kind [`std::io::ErrorKind::UnexpectedEof`] is returned.
+	pub fn content_eq(&self, other: &MemFile) -> std::io::Result<bool> {
+		let a = Contents::open(self)?;
+		let b = Contents::open(other)?;
+		if a.len != b.len {
+			return Ok(false);
+		}
+		if a.same_file(&b) {
+			return Ok(true);
+		}
+
+		let mut buffer_a = Vec::new();
+		let mut buffer_b = Vec::new();
+		let mut offset = 0;
+		while offset < a.len {
+			let chunk_a = a.chunk(offset, &mut buffer_a)?;
+			let chunk_b = b.chunk(offset, &mut buffer_b)?;
+			if chunk_a != chunk_b {
+				return Ok(false);
+			}
+			offset += chunk_a.len() as u64;
+		}
+		Ok(true)
+	}
+}
+
+/// A digest of the contents of a file that can not change anymore.
+///
+/// A [`FrozenDigest`] can only be computed for a file that is sealed with [`Seal::Write`], [`Seal::Shrink`] and [`Seal::Grow`].
+/// Those seals can never be removed, so the digest stays valid for as long as the file exists,
+/// and it can safely be used for deduplication or integrity checks.
+///
+/// The digest is computed with a [`Hasher`] of your choice.
+/// The length of the file is recorded alongside the digest and is also compared by the [`PartialEq`] implementation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FrozenDigest {
+	digest: u64,
+	len: u64,
+}
+
+impl FrozenDigest {
+	/// The seals that a file must have before a digest can be computed.
+	pub const REQUIRED_SEALS: Seals = Seals::from_bits_truncate(Seal::Write as u32 | Seal::Shrink as u32 | Seal::Grow as u32);
+
+	/// Compute the digest of a sealed file using the given hasher.
+	///
+	/// This function fails with [`Error::MissingSeals`] if the file is not sealed with all of [`Self::REQUIRED_SEALS`].
+	pub fn compute(file: &MemFile, mut hasher: impl Hasher) -> Result<Self, Error> {
+		file.require_seals(Self::REQUIRED_SEALS)?;
+		let len = file.metadata()?.len();
+		file.content_hash(&mut hasher)?;
+		Ok(Self {
+			digest: hasher.finish(),
+			len,
+		})
+	}
+
+	/// Get the digest produced by the hasher.
+	pub fn digest(&self) -> u64 {
+		self.digest
+	}
+
+	/// Get the length of the file when the digest was computed.
+	///
+	/// Since the file is sealed against resizing, this is also the current length of the file.
+	pub fn len(&self) -> u64 {
+		self.len
+	}
+
+	/// Check if the digest was computed for an empty file.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+}
+
+/// The contents of a file, accessed through a memory mapping if that is safe, or with positional reads otherwise.
+struct Contents<'a> {
+	file: &'a MemFile,
+	metadata: std::fs::Metadata,
+	len: u64,
+	mapping: Option<RawMapping>,
+	chunk_size: usize,
+}
+
+impl<'a> Contents<'a> {
+	/// Prepare to read the contents of a file.
+	fn open(file: &'a MemFile) -> std::io::Result<Self> {
+		let metadata = file.metadata()?;
+		let len = metadata.len();
+
+		// Only map the file if it can not be shrunk or modified while we read it.
+		let frozen = file.get_seals_raw()
+			.is_ok_and(|seals| seals.contains(Seal::Write | Seal::Shrink));
+		let mapping = match usize::try_from(len) {
+			Ok(len) if frozen && len > 0 => Some(RawMapping::new(file.as_fd(), len, libc::PROT_READ, libc::MAP_SHARED)?),
+			_ => None,
+		};
+
+		Ok(Self {
+			file,
+			metadata,
+			len,
+			mapping,
+			chunk_size: sys::page_size(),
+		})
+	}
+
+	/// Check if both contents refer to the same file.
+	fn same_file(&self, other: &Self) -> bool {
+		self.metadata.dev() == other.metadata.dev() && self.metadata.ino() == other.metadata.ino()
+	}
+
+	/// Get the chunk of data starting at `offset`.
+	///
+	/// The `buffer` is used for positional reads if the file is not mapped.
+	fn chunk<'b>(&'b self, offset: u64, buffer: &'b mut Vec<u8>) -> std::io::Result<&'b [u8]> {
+		let len = (self.len - offset).min(self.chunk_size as u64) as usize;
+		if let Some(mapping) = &self.mapping {
+			// The file is sealed against writing and shrinking, so the mapped data can not change.
+			let data = unsafe { std::slice::from_raw_parts(mapping.as_ptr(), mapping.len()) };
+			let offset = offset as usize;
+			Ok(&data[offset..offset + len])
+		} else {
+			buffer.resize(len, 0);
+			self.file.read_exact_at(buffer, offset)?;
+			Ok(buffer)
+		}
+	}
+}
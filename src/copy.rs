@@ -0,0 +1,229 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::os::fd::{AsFd, BorrowedFd};
+use std::os::unix::fs::FileExt;
+
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+
+#[cfg(target_os = "linux")]
+use crate::sys;
+use crate::{CreateOptions, Error, MemFile, Seals};
+
+/// Maximum number of bytes to transfer with a single syscall.
+const MAX_CHUNK: u64 = 1 << 30;
+
+/// Size of the buffer used when falling back to read and write calls.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+impl MemFile {
+	/// Create a new [`MemFile`] with a copy of the current contents of this file.
+	///
+	/// The new file is created with sealing support, and the given `seals` are added after the contents have been copied.
+	/// It uses the same [transparent huge page mode][Self::transparent_huge_pages] as this file.
+	/// The contents are copied with [`Self::copy_to_fd()`], which avoids copying through userspace where possible.
+	///
+	/// The copy is not atomic.
+	/// If the contents of this file are modified while the snapshot is being created, the snapshot may contain a mix of old and new data.
+	/// Seal this file with [`Seal::Write`][crate::Seal::Write] or make sure it is not modified to get a consistent snapshot.
+	///
+	/// The file position of the returned [`MemFile`] is at the start of the file.
+	/// The file position of this [`MemFile`] is not changed.
+	pub fn snapshot(&self, name: &str, seals: impl Into<Seals>) -> Result<MemFile, Error> {
+		let options = CreateOptions::new()
+			.allow_sealing(true)
+			.transparent_huge_pages(self.transparent_huge_pages());
+		let mut snapshot = MemFile::create(name, options)?;
+		let len = self.metadata()?.len();
+		self.copy_to_fd(&snapshot, 0..len)?;
+		std::io::Seek::rewind(&mut snapshot)?;
+
+		let seals = seals.into();
+		if !seals.is_empty() {
+			snapshot.add_seals(seals)?;
+		}
+		Ok(snapshot)
+	}
+
+	/// Copy data from another file descriptor into a range of this file.
+	///
+	/// Data is read from the current position of `src` and written to this file starting at `range.start`.
+	/// At most `range.end - range.start` bytes are copied, but fewer bytes are copied if the end of `src` is reached first.
+	/// The file position of `src` is advanced by the number of bytes copied.
+	/// The file position of this [`MemFile`] is not changed.
+	///
+	/// On Linux, the data is copied inside the kernel with `copy_file_range` if possible,
+	/// or with `splice` if `src` is a pipe.
+	/// Otherwise, this function falls back to reading and writing through a userspace buffer.
+	///
+	/// If the file has a [maximum size][crate::CreateOptions::max_size], the copy stops when the maximum size is reached.
+	///
+	/// Returns the number of bytes copied.
+	pub fn copy_from_fd(&self, src: impl AsFd, range: Range<u64>) -> std::io::Result<u64> {
+		check_range(&range)?;
+		let range = match self.max_size() {
+			Some(max_size) if range.start < max_size => range.start..range.end.min(max_size),
+			_ => range,
+		};
+		let result = self.copy_from_fd_limited(src.as_fd(), range);
+		self.update_accounting();
+		result
+	}
+
+	/// Copy data from another file descriptor into a range of this file that does not cross the maximum size of the file.
+	fn copy_from_fd_limited(&self, src: BorrowedFd, range: Range<u64>) -> std::io::Result<u64> {
+		let mut offset = range.start;
+
+		#[cfg(target_os = "linux")]
+		{
+			let done = try_transfer(&mut offset, range.end, |offset, len| {
+				let len = self.limit_write(len, || Ok(offset))?;
+				let mut offset = to_loff(offset)?;
+				sys::copy_file_range(src.as_raw_fd(), None, self.as_raw_fd(), Some(&mut offset), len)
+			})?;
+			if done {
+				return Ok(offset - range.start);
+			}
+
+			let done = try_transfer(&mut offset, range.end, |offset, len| {
+				let len = self.limit_write(len, || Ok(offset))?;
+				let mut offset = to_loff(offset)?;
+				sys::splice(src.as_raw_fd(), None, self.as_raw_fd(), Some(&mut offset), len)
+			})?;
+			if done {
+				return Ok(offset - range.start);
+			}
+		}
+
+		// The duplicated file descriptor shares the file position with the original.
+		let mut src = File::from(src.try_clone_to_owned()?);
+		let mut buffer = vec![0; buffer_size(offset, range.end)];
+		transfer(&mut offset, range.end, |offset, len| {
+			let len = self.limit_write(len.min(buffer.len()), || Ok(offset))?;
+			let read = src.read(&mut buffer[..len])?;
+			self.file.write_all_at(&buffer[..read], offset)?;
+			Ok(read)
+		})?;
+		Ok(offset - range.start)
+	}
+
+	/// Copy data from a range of this file to another file descriptor.
+	///
+	/// Data is read from this file starting at `range.start` and written to the current position of `dst`.
+	/// At most `range.end - range.start` bytes are copied, but fewer bytes are copied if the end of this file is reached first.
+	/// The file position of `dst` is advanced by the number of bytes copied.
+	/// The file position of this [`MemFile`] is not changed.
+	///
+	/// On Linux, the data is copied inside the kernel with `copy_file_range` if possible,
+	/// or with `sendfile` or `splice` for sockets and pipes.
+	/// Otherwise, this function falls back to reading and writing through a userspace buffer.
+	///
+	/// Returns the number of bytes copied.
+	pub fn copy_to_fd(&self, dst: impl AsFd, range: Range<u64>) -> std::io::Result<u64> {
+		let dst = dst.as_fd();
+		check_range(&range)?;
+		let mut offset = range.start;
+
+		#[cfg(target_os = "linux")]
+		{
+			let done = try_transfer(&mut offset, range.end, |offset, len| {
+				let mut offset = to_loff(offset)?;
+				sys::copy_file_range(self.as_raw_fd(), Some(&mut offset), dst.as_raw_fd(), None, len)
+			})?;
+			if done {
+				return Ok(offset - range.start);
+			}
+
+			let done = try_transfer(&mut offset, range.end, |offset, len| {
+				let mut offset = sys::to_off_t(offset)?;
+				sys::sendfile(dst.as_raw_fd(), self.as_raw_fd(), &mut offset, len)
+			})?;
+			if done {
+				return Ok(offset - range.start);
+			}
+
+			let done = try_transfer(&mut offset, range.end, |offset, len| {
+				let mut offset = to_loff(offset)?;
+				sys::splice(self.as_raw_fd(), Some(&mut offset), dst.as_raw_fd(), None, len)
+			})?;
+			if done {
+				return Ok(offset - range.start);
+			}
+		}
+
+		// The duplicated file descriptor shares the file position with the original.
+		let mut dst = File::from(dst.try_clone_to_owned()?);
+		let mut buffer = vec![0; buffer_size(offset, range.end)];
+		transfer(&mut offset, range.end, |offset, len| {
+			let len = len.min(buffer.len());
+			let read = self.file.read_at(&mut buffer[..len], offset)?;
+			dst.write_all(&buffer[..read])?;
+			Ok(read)
+		})?;
+		Ok(offset - range.start)
+	}
+}
+
+/// Check that a range is not reversed.
+fn check_range(range: &Range<u64>) -> std::io::Result<()> {
+	if range.start > range.end {
+		Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "range start is past the range end"))
+	} else {
+		Ok(())
+	}
+}
+
+/// Get the size for a userspace copy buffer.
+fn buffer_size(offset: u64, end: u64) -> usize {
+	(end - offset).min(BUFFER_SIZE as u64) as usize
+}
+
+/// Repeatedly call `f` to transfer data until `end` is reached or `f` reports the end of the input.
+///
+/// The function is called with the current offset and the maximum number of bytes to transfer.
+/// It should return the number of bytes transferred, or 0 to signal the end of the input.
+fn transfer(offset: &mut u64, end: u64, mut f: impl FnMut(u64, usize) -> std::io::Result<usize>) -> std::io::Result<()> {
+	while *offset < end {
+		let len = (end - *offset).min(MAX_CHUNK) as usize;
+		match f(*offset, len) {
+			Ok(0) => break,
+			Ok(transferred) => *offset += transferred as u64,
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(e) => return Err(e),
+		}
+	}
+	Ok(())
+}
+
+/// Transfer data with a syscall that may not support the given file descriptors.
+///
+/// Returns `Ok(false)` if the caller should fall back to a different method for the remaining data.
+///
+/// Some kernels report the end of the input for files that do not support the syscall,
+/// so an immediate end of input also triggers a fallback to let the next method confirm it.
+#[cfg(target_os = "linux")]
+fn try_transfer(offset: &mut u64, end: u64, f: impl FnMut(u64, usize) -> std::io::Result<usize>) -> std::io::Result<bool> {
+	let start = *offset;
+	match transfer(offset, end, f) {
+		Ok(()) => Ok(*offset != start || start == end),
+		Err(e) if is_unsupported(&e) => Ok(false),
+		Err(e) => Err(e),
+	}
+}
+
+/// Check if an error indicates that a syscall does not support the given file descriptors.
+#[cfg(target_os = "linux")]
+fn is_unsupported(error: &std::io::Error) -> bool {
+	matches!(
+		error.raw_os_error(),
+		Some(libc::EINVAL | libc::EXDEV | libc::ENOSYS | libc::EOPNOTSUPP | libc::ESPIPE | libc::EBADF)
+	)
+}
+
+/// Convert an offset to a `loff_t`.
+#[cfg(target_os = "linux")]
+fn to_loff(offset: u64) -> std::io::Result<libc::loff_t> {
+	libc::loff_t::try_from(offset)
+		.map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidInput))
+}
@@ -0,0 +1,161 @@
+use crate::{CreateOptions, MemFileName, Seal, Seals};
+
+/// Error that can occur when creating or manipulating a [`MemFile`][crate::MemFile].
+///
+/// When a syscall fails with an ambiguous error code, the cause is diagnosed with follow-up checks,
+/// such as querying the active seals of the file.
+/// If the cause can not be determined, the original I/O error is returned as [`Error::Io`].
+///
+/// The error can be converted into a [`std::io::Error`],
+/// so you can pass it up using the `?` operator from a function that returns an [`std::io::Result`].
+/// The [`Error`] can be recovered from the I/O error with [`std::io::Error::get_ref()`] and [`downcast_ref()`](std::error::Error#method.downcast_ref).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+	/// No more seals can be added to the file.
+	///
+	/// Either the file was created without [`CreateOptions::allow_sealing()`], or the [`Seal::Seal`] seal has been added.
+	SealingNotAllowed,
+
+	/// The operation is prevented by the given seals on the file.
+	SealedAgainst(Seals),
+
+	/// The operation requires the given seals, but they are not present on the file.
+	MissingSeals(Seals),
+
+	/// The [`Seal::Write`] seal can not be added because a shared, writable memory mapping exists for the file.
+	WritableMappingExists,
+
+	/// The file descriptor does not refer to a file that was created by `memfd_create`.
+	NotAMemfd,
+
+	/// The requested huge page size is not supported by the CPU or kernel configuration.
+	HugeTlbUnavailable,
+
+	/// Transparent huge pages are required, but not supported or disabled by the system.
+	TransparentHugePagesUnavailable,
+
+	/// The operation would grow the file beyond the maximum size set with [`CreateOptions::max_size()`].
+	SizeLimitExceeded(u64),
+
+	/// The operation would exceed the [process-wide budget][crate::budget] for the total size of all files.
+	BudgetExceeded,
+
+	/// The name for the file is longer than the 249 bytes allowed by the kernel.
+	NameTooLong,
+
+	/// The name for the file contains a null byte.
+	NameContainsNul,
+
+	/// Some other I/O error occurred.
+	Io(std::io::Error),
+}
+
+impl Error {
+	/// Get the [`std::io::ErrorKind`] that corresponds to this error.
+	pub fn kind(&self) -> std::io::ErrorKind {
+		match self {
+			Self::SealingNotAllowed => std::io::ErrorKind::PermissionDenied,
+			Self::SealedAgainst(_) => std::io::ErrorKind::PermissionDenied,
+			Self::MissingSeals(_) => std::io::ErrorKind::InvalidInput,
+			Self::WritableMappingExists => std::io::ErrorKind::ResourceBusy,
+			Self::NotAMemfd => std::io::ErrorKind::InvalidInput,
+			Self::HugeTlbUnavailable => std::io::ErrorKind::Unsupported,
+			Self::TransparentHugePagesUnavailable => std::io::ErrorKind::Unsupported,
+			Self::SizeLimitExceeded(_) => std::io::ErrorKind::FileTooLarge,
+			Self::BudgetExceeded => std::io::ErrorKind::QuotaExceeded,
+			Self::NameTooLong => std::io::ErrorKind::InvalidInput,
+			Self::NameContainsNul => std::io::ErrorKind::InvalidInput,
+			Self::Io(e) => e.kind(),
+		}
+	}
+
+	/// Diagnose an error from `memfd_create`.
+	pub(crate) fn from_create(error: std::io::Error, name_len: usize, options: &CreateOptions) -> Self {
+		match error.raw_os_error() {
+			Some(libc::EINVAL) if name_len > MemFileName::MAX_LEN => Self::NameTooLong,
+			Some(libc::EINVAL | libc::ENODEV | libc::ENOENT) if options.huge_table.is_some() => Self::HugeTlbUnavailable,
+			_ => Self::Io(error),
+		}
+	}
+
+	/// Diagnose an error from adding seals to a file.
+	pub(crate) fn from_add_seals(error: std::io::Error, current_seals: impl FnOnce() -> std::io::Result<Seals>) -> Self {
+		match error.raw_os_error() {
+			Some(libc::EBUSY) => Self::WritableMappingExists,
+			Some(libc::EPERM) => match current_seals() {
+				Ok(seals) if seals.contains(Seal::Seal) => Self::SealingNotAllowed,
+				_ => Self::Io(error),
+			},
+			Some(libc::EINVAL) => match current_seals() {
+				Err(e) if e.raw_os_error() == Some(libc::EINVAL) => Self::NotAMemfd,
+				_ => Self::Io(error),
+			},
+			_ => Self::Io(error),
+		}
+	}
+
+	/// Diagnose an error from getting the seals of a file.
+	pub(crate) fn from_get_seals(error: std::io::Error) -> Self {
+		match error.raw_os_error() {
+			Some(libc::EINVAL) => Self::NotAMemfd,
+			_ => Self::Io(error),
+		}
+	}
+
+	/// Diagnose an `EPERM` error from an operation that may be prevented by some seals.
+	///
+	/// If the file has any of the `relevant` seals, an [`Error::SealedAgainst`] is returned with the active relevant seals.
+	pub(crate) fn from_sealed(error: std::io::Error, relevant: Seals, current_seals: impl FnOnce() -> std::io::Result<Seals>) -> Self {
+		if error.raw_os_error() != Some(libc::EPERM) {
+			return Self::Io(error);
+		}
+		match current_seals() {
+			Ok(seals) if seals.intersects(relevant) => Self::SealedAgainst(seals & relevant),
+			_ => Self::Io(error),
+		}
+	}
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Self::SealingNotAllowed => write!(f, "sealing is not allowed for the file"),
+			Self::SealedAgainst(seals) => write!(f, "operation is prevented by seals on the file: {:?}", seals),
+			Self::MissingSeals(seals) => write!(f, "operation requires seals that are not present on the file: {:?}", seals),
+			Self::WritableMappingExists => write!(f, "can not add write seal while a shared, writable memory mapping exists"),
+			Self::NotAMemfd => write!(f, "file was not created by memfd_create"),
+			Self::HugeTlbUnavailable => write!(f, "the requested huge page size is not available"),
+			Self::TransparentHugePagesUnavailable => write!(f, "transparent huge pages are not available for shared memory"),
+			Self::SizeLimitExceeded(max_size) => write!(f, "operation would grow the file beyond its maximum size of {} bytes", max_size),
+			Self::BudgetExceeded => write!(f, "operation would exceed the memory budget for files in this process"),
+			Self::NameTooLong => write!(f, "file name exceeds the maximum length of {} bytes", MemFileName::MAX_LEN),
+			Self::NameContainsNul => write!(f, "file name contains a null byte"),
+			Self::Io(e) => e.fmt(f),
+		}
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(e) => e.source(),
+			_ => None,
+		}
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(other: std::io::Error) -> Self {
+		Self::Io(other)
+	}
+}
+
+impl From<Error> for std::io::Error {
+	fn from(other: Error) -> Self {
+		match other {
+			Error::Io(e) => e,
+			other => std::io::Error::new(other.kind(), other),
+		}
+	}
+}
@@ -0,0 +1,121 @@
+use crate::mapping::RawMapping;
+use crate::{sys, MemFile, Seal};
+
+/// A read-only memory mapping of a [`MemFile`] that reports an error instead of crashing when the file has been shrunk.
+///
+/// Accessing the memory mapping of a file beyond its current size raises a `SIGBUS` signal, which normally kills the process.
+/// That can only be prevented by sealing the file with [`Seal::Shrink`], as required by [`GrowableMapping`][crate::GrowableMapping].
+/// If you can not require that seal, for example because the file is received from an untrusted peer, use this mapping instead.
+///
+/// The mapped memory can not be accessed directly.
+/// Instead, [`Self::read_at()`] and [`Self::read_exact_at()`] copy the data with the `process_vm_readv` syscall on the current process.
+/// The kernel reports an error for pages beyond the end of the file instead of raising a signal.
+/// This makes every read a syscall, so this is mostly useful for larger reads.
+///
+/// Note that the pages are mapped as a whole, so the bytes after the end of the file in the last page of the file read as zero.
+///
+/// This type is only available on Linux and Android.
+#[derive(Debug)]
+pub struct FallibleMapping {
+	file: MemFile,
+	mapping: RawMapping,
+}
+
+impl FallibleMapping {
+	/// Map the whole file in memory.
+	///
+	/// The file does not need to have any seals.
+	pub fn new(file: MemFile) -> std::io::Result<Self> {
+		let mut mapping = Self {
+			file,
+			mapping: RawMapping::empty(),
+		};
+		mapping.refresh()?;
+		Ok(mapping)
+	}
+
+	/// Resize the mapping to the current size of the file.
+	///
+	/// The current size of the file is retrieved using [`MemFile::metadata()`].
+	/// Reads beyond the end of the mapping fail even if the file has grown, until the mapping is refreshed.
+	///
+	/// Returns `true` if the mapping was changed.
+	pub fn refresh(&mut self) -> std::io::Result<bool> {
+		let len = usize::try_from(self.file.metadata()?.len())
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "file is too large to map in memory"))?;
+		if len == self.mapping.len() {
+			return Ok(false);
+		}
+		self.mapping.remap(self.file.as_fd(), len, libc::PROT_READ, libc::MAP_SHARED)?;
+		Ok(true)
+	}
+
+	/// Get the length of the mapping in bytes.
+	///
+	/// This is the size of the file when the mapping was last refreshed.
+	/// The file may have been shrunk since then.
+	pub fn len(&self) -> usize {
+		self.mapping.len()
+	}
+
+	/// Check if the mapping is empty.
+	pub fn is_empty(&self) -> bool {
+		self.mapping.len() == 0
+	}
+
+	/// Read data from the mapping at the given offset.
+	///
+	/// Returns the number of bytes read, which is less than the size of `buffer` if the read reaches the end of the mapping or the end of the file.
+	/// Returns `0` if `offset` is at or beyond the end of the mapping or the end of the file.
+	pub fn read_at(&self, buffer: &mut [u8], offset: usize) -> std::io::Result<usize> {
+		let len = buffer.len().min(self.mapping.len().saturating_sub(offset));
+		if len == 0 {
+			return Ok(0);
+		}
+		let source = self.mapping.as_ptr().wrapping_add(offset);
+		match sys::read_own_memory(source, &mut buffer[..len]) {
+			Ok(read) => Ok(read),
+			Err(e) if e.raw_os_error() == Some(libc::EFAULT) => Ok(0),
+			Err(e) => Err(e),
+		}
+	}
+
+	/// Read the exact number of bytes required to fill `buffer` from the given offset.
+	///
+	/// If the data extends beyond the end of the mapping or the end of the file,
+	/// an error of kind [`std::io::ErrorKind::UnexpectedEof`] is returned.
+	pub fn read_exact_at(&self, mut buffer: &mut [u8], mut offset: usize) -> std::io::Result<()> {
+		while !buffer.is_empty() {
+			match self.read_at(buffer, offset)? {
+				0 => return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "read beyond the end of the file")),
+				read => {
+					buffer = &mut buffer[read..];
+					offset += read;
+				},
+			}
+		}
+		Ok(())
+	}
+
+	/// Get the [`MemFile`] that is mapped.
+	pub fn file(&self) -> &MemFile {
+		&self.file
+	}
+
+	/// Remove the mapping and return the [`MemFile`].
+	pub fn into_file(self) -> MemFile {
+		self.file
+	}
+}
+
+impl MemFile {
+	/// Check if memory mappings of the file can raise a `SIGBUS` signal because the file is shrunk.
+	///
+	/// This returns `false` only if the file is sealed with [`Seal::Shrink`].
+	/// Otherwise, any process with access to the file can shrink it,
+	/// and you should use a [`FallibleMapping`] to read from it safely.
+	pub fn may_shrink(&self) -> bool {
+		!self.get_seals_raw()
+			.is_ok_and(|seals| seals.contains(Seal::Shrink))
+	}
+}
@@ -41,6 +41,13 @@
 //! When sharing the file with other processes, it prevents those processes from shrinking or writing to the file,
 //! while the original process can still change the file contents.
 //!
+//! # Optional features
+//! * `rustix`: use [`rustix`](https://docs.rs/rustix) instead of `libc` to create files and to manage seals.
+//!   This removes all `unsafe` code from those operations, but other functionality still uses `libc`.
+//! * `arrow`: enable the `arrow` module to exchange Arrow record batches in sealed files without copying.
+//! * `bytes`: add `MemFile::into_bytes()` to convert a sealed file into [`bytes::Bytes`](https://docs.rs/bytes) backed by a memory mapping.
+//! * `allocator-api2`: implement [`allocator_api2::alloc::Allocator`](https://docs.rs/allocator-api2) for [`MemFileArena`].
+//!
 //! # Example
 //! ```
 //! # fn main() -> std::io::Result<()> {
@@ -59,11 +66,69 @@ use std::ffi::CStr;
 use std::fs::File;
 use std::os::fd::{BorrowedFd, AsFd, OwnedFd};
 use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::sync::Arc;
 
 mod sys;
 mod seal;
+mod capabilities;
+mod copy;
+mod vectored;
+mod persist;
+mod backend;
+mod error;
+mod name;
+pub mod registry;
+mod pool;
+mod mapping;
+pub mod log;
+mod advice;
+mod thp;
+mod content;
+pub mod wayland;
+mod arena;
+pub mod relocatable;
+mod usage;
+pub mod budget;
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+
+#[cfg(feature = "bytes")]
+mod bytes_mapping;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod smaps;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod fallible;
+
+#[cfg(target_os = "linux")]
+mod secret;
 
 pub use seal::{Seal, Seals};
+pub use capabilities::Capabilities;
+pub use backend::Backend;
+pub use error::Error;
+pub use name::MemFileName;
+pub use pool::{MemFilePool, PoolStats};
+pub use mapping::{GrowableMapping, PrivateMapping};
+pub use advice::Advice;
+pub use thp::{ShmemThpSetting, ThpMode};
+pub use content::FrozenDigest;
+pub use arena::{MemFileArena, ShmPtr};
+pub use usage::MemoryUsage;
+
+#[cfg(target_os = "linux")]
+pub use secret::SecretMemFile;
+
+#[cfg(target_os = "linux")]
+pub use advice::MemoryPolicy;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use vectored::RwFlags;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use fallible::FallibleMapping;
 
 /// A memory backed file that can have seals applied to it.
 ///
@@ -72,6 +137,9 @@ pub use seal::{Seal, Seals};
 #[derive(Debug)]
 pub struct MemFile {
 	file: File,
+	backend: Backend,
+	thp_mode: Option<ThpMode>,
+	accounting: Option<Arc<budget::Accounting>>,
 }
 
 impl MemFile {
@@ -84,18 +152,51 @@ impl MemFile {
 	/// The close-on-exec flag is set on the created file descriptor.
 	/// If you want to pass it to a child process, you should use [`libc::dup2`] or something similar *after forking*.
 	/// Disabling the close-on-exec flag before forking causes a race condition with other threads.
-	pub fn create(name: &str, options: CreateOptions) -> std::io::Result<Self> {
-		let file = sys::memfd_create(name, options.as_flags())?;
-		Ok(Self { file })
+	///
+	/// The name is validated before the file is created.
+	/// This function fails with [`Error::NameContainsNul`] if the name contains a null byte,
+	/// or with [`Error::NameTooLong`] if the name is longer than [`MemFileName::MAX_LEN`] bytes,
+	/// unless name truncation is enabled with [`CreateOptions::truncate_name()`].
+	/// It fails with [`Error::HugeTlbUnavailable`] if the requested huge page size is not available,
+	/// and with [`Error::BudgetExceeded`] if the [process-wide budget][budget] has been used up.
+	pub fn create(name: &str, options: CreateOptions) -> Result<Self, Error> {
+		let name = if options.truncate_name {
+			MemFileName::new_truncated(name)?
+		} else {
+			MemFileName::new(name)?
+		};
+		Self::create_named(&name, options)
 	}
 
 	/// Create a new [`MemFile`] with the given options.
 	///
 	/// This is identical to [`Self::create`], except that it takes the name as [`CStr`] to avoid allocations.
 	/// See that function for more information.
-	pub fn create_cstr(name: &CStr, options: CreateOptions) -> std::io::Result<Self> {
-		let file = sys::memfd_create_cstr(name, options.as_flags())?;
-		Ok(Self { file })
+	pub fn create_cstr(name: &CStr, options: CreateOptions) -> Result<Self, Error> {
+		let name = if options.truncate_name {
+			MemFileName::from_cstr_truncated(name)
+		} else {
+			MemFileName::from_cstr(name)?
+		};
+		Self::create_named(&name, options)
+	}
+
+	/// Create a new [`MemFile`] with a pre-validated name.
+	///
+	/// This is identical to [`Self::create`], except that it takes a [`MemFileName`] which has already been validated.
+	/// See that function for more information.
+	pub fn create_named(name: &MemFileName<'_>, options: CreateOptions) -> Result<Self, Error> {
+		if let Some(mode) = options.thp_mode {
+			if options.huge_table.is_some() {
+				return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "transparent huge pages can not be combined with hugetlbfs").into());
+			}
+			thp::check(mode)?;
+		}
+		let accounting = budget::Accounting::new(options.max_size)?;
+		let name = name.as_cstr();
+		let (file, backend) = backend::create(name, &options)
+			.map_err(|e| Error::from_create(e, name.to_bytes().len(), &options))?;
+		Ok(Self { file, backend, thp_mode: options.thp_mode, accounting: Some(Arc::new(accounting)) })
 	}
 
 	/// Create a new [`MemFile`] with default options.
@@ -103,7 +204,7 @@ impl MemFile {
 	/// Sealing is not enabled for the created file.
 	///
 	/// See [`Self::create`] for more information.
-	pub fn create_default(name: &str) -> std::io::Result<Self> {
+	pub fn create_default(name: &str) -> Result<Self, Error> {
 		Self::create(name, CreateOptions::default())
 	}
 
@@ -113,7 +214,7 @@ impl MemFile {
 	/// All other options are the same as the defaults.
 	///
 	/// See [`Self::create`] for more information.
-	pub fn create_sealable(name: &str) -> std::io::Result<Self> {
+	pub fn create_sealable(name: &str) -> Result<Self, Error> {
 		Self::create(name, CreateOptions::new().allow_sealing(true))
 	}
 
@@ -123,21 +224,22 @@ impl MemFile {
 	/// but reads, writes, and seeks will affect both [`MemFile`] instances simultaneously.
 	pub fn try_clone(&self) -> std::io::Result<Self> {
 		let file = self.file.try_clone()?;
-		Ok(Self { file })
+		Ok(Self { file, backend: self.backend, thp_mode: self.thp_mode, accounting: self.accounting.clone() })
 	}
 
 	/// Wrap an already-open [`OwnedFd`] as [`MemFile`].
 	///
 	/// This function returns an error if the file was not created by `memfd_create`.
+	/// The backend of the returned [`MemFile`] is always reported as [`Backend::MemfdCreate`].
 	///
 	/// If the function succeeds, the passed in file object is consumed and the returned [`MemFile`] takes ownership of the file descriptor.
 	/// If the function fails, the original [`OwnedFd`] is included in the returned error.
 	pub fn from_fd(fd: OwnedFd) -> Result<Self, FromFdError> {
-		match sys::memfd_get_seals(fd.as_raw_fd()) {
-			Err(error) => Err(FromFdError { error, fd }),
+		match sys::memfd_get_seals(fd.as_fd()) {
+			Err(error) => Err(FromFdError { error: Error::from_get_seals(error).into(), fd }),
 			Ok(_) => {
 				let file = File::from(fd);
-				Ok(Self { file })
+				Ok(Self { file, backend: Backend::MemfdCreate, thp_mode: None, accounting: None })
 			}
 		}
 	}
@@ -145,8 +247,10 @@ impl MemFile {
 	/// Convert this [`MemFile`] into an [`OwnedFd`].
 	///
 	/// This may be useful for interoperability with other crates.
+	///
+	/// The file stays charged to the [`budget`] for its current size, even after the file descriptor has been closed.
 	pub fn into_fd(self) -> OwnedFd {
-		self.file.into()
+		self.into_untracked_file().into()
 	}
 
 	/// Convert this [`MemFile`] into an [`OwnedFd`].
@@ -159,10 +263,47 @@ impl MemFile {
 	/// Convert this [`MemFile`] into an [`std::fs::File`].
 	///
 	/// This may be useful for interoperability with other crates.
+	///
+	/// The file stays charged to the [`budget`] for its current size, even after the file has been closed.
 	pub fn into_file(self) -> std::fs::File {
+		self.into_untracked_file()
+	}
+
+	/// Convert this [`MemFile`] into a [`File`] that is no longer tracked by the [`budget`].
+	///
+	/// There is no way to know when the returned file is closed, so the charge for the file is never returned to the budget.
+	fn into_untracked_file(self) -> File {
+		if let Some(accounting) = self.accounting {
+			std::mem::forget(accounting);
+		}
 		self.file
 	}
 
+	/// Get the backend that was used to create the file.
+	///
+	/// This is [`Backend::MemfdCreate`] unless `memfd_create` was unavailable and the file was created with a fallback mechanism,
+	/// or a different backend was requested with [`CreateOptions::backend()`].
+	/// Only files created with `memfd_create` support sealing.
+	pub fn backend(&self) -> Backend {
+		self.backend
+	}
+
+	/// Get the transparent huge page mode of the file.
+	///
+	/// This is the mode set with [`CreateOptions::transparent_huge_pages()`] when the file was created.
+	/// It is always `None` for files created with [`Self::from_fd()`].
+	pub fn transparent_huge_pages(&self) -> Option<ThpMode> {
+		self.thp_mode
+	}
+
+	/// Get the maximum size of the file.
+	///
+	/// This is the size set with [`CreateOptions::max_size()`] when the file was created.
+	/// It is always `None` for files created with [`Self::from_fd()`].
+	pub fn max_size(&self) -> Option<u64> {
+		self.accounting.as_ref()?.max_size()
+	}
+
 	/// Query metadata about the underlying file.
 	///
 	/// Note that not all information in the metadata is not very meaningfull for a `memfd`.
@@ -178,13 +319,36 @@ impl MemFile {
 	/// If it is greater than the current file's size, then the file will be extended to size and have all of the intermediate data filled in with 0s.
 	/// The file's cursor isn't changed.
 	/// In particular, if the cursor was at the end and the file is shrunk using this operation, the cursor will now be past the end.
-	pub fn set_len(&self, size: u64) -> std::io::Result<()> {
-		self.file.set_len(size)
+	///
+	/// This function fails with [`Error::SealedAgainst`] if the file is sealed with [`Seal::Shrink`] or [`Seal::Grow`] and the resize is not allowed.
+	/// It fails with [`Error::SizeLimitExceeded`] if `size` exceeds the [maximum size][CreateOptions::max_size] of the file,
+	/// and with [`Error::BudgetExceeded`] if growing the file would exceed the [process-wide budget][budget].
+	pub fn set_len(&self, size: u64) -> Result<(), Error> {
+		if let Some(accounting) = &self.accounting {
+			accounting.grow_to(size)?;
+		}
+		let result = self.file.set_len(size).map_err(|e| {
+			let relevant = match self.metadata() {
+				Ok(metadata) if size < metadata.len() => Seals::from(Seal::Shrink),
+				Ok(_) => Seals::from(Seal::Grow),
+				Err(_) => Seal::Shrink | Seal::Grow,
+			};
+			Error::from_sealed(e, relevant, || self.get_seals_raw())
+		});
+		self.update_accounting();
+		result
 	}
 
 	/// Get the active seals of the file.
-	pub fn get_seals(&self) -> std::io::Result<Seals> {
-		let seals = sys::memfd_get_seals(self.as_raw_fd())?;
+	///
+	/// This function fails with [`Error::NotAMemfd`] if the file descriptor does not support seals at all.
+	pub fn get_seals(&self) -> Result<Seals, Error> {
+		self.get_seals_raw().map_err(Error::from_get_seals)
+	}
+
+	/// Get the active seals of the file without diagnosing errors.
+	fn get_seals_raw(&self) -> std::io::Result<Seals> {
+		let seals = sys::memfd_get_seals(self.as_fd())?;
 		Ok(Seals::from_bits_truncate(seals as u32))
 	}
 
@@ -197,25 +361,48 @@ impl MemFile {
 	/// or if you try to add [`Seal::Write`] while a shared, writable memory mapping exists for the file.
 	///
 	/// Adding a seal that is already active is a no-op.
-	pub fn add_seal(&self, seal: Seal) -> std::io::Result<()> {
+	pub fn add_seal(&self, seal: Seal) -> Result<(), Error> {
 		self.add_seals(seal.into())
 	}
 
 	/// Add multiple seals to the file.
 	///
-	/// This function will fail if the file was not created with sealing support,
-	/// if the file has already been sealed with [`Seal::Seal`],
-	/// or if you try to add [`Seal::Write`] while a shared, writable memory mapping exists for the file.
+	/// This function will fail with [`Error::SealingNotAllowed`] if the file was not created with sealing support,
+	/// or if the file has already been sealed with [`Seal::Seal`].
+	/// It will fail with [`Error::WritableMappingExists`] if you try to add [`Seal::Write`] while a shared, writable memory mapping exists for the file.
 	///
 	/// Adding seals that are already active is a no-op.
-	pub fn add_seals(&self, seals: Seals) -> std::io::Result<()> {
-		sys::memfd_add_seals(self.as_raw_fd(), seals.bits() as std::os::raw::c_int)
+	pub fn add_seals(&self, seals: Seals) -> Result<(), Error> {
+		sys::memfd_add_seals(self.as_fd(), seals.bits() as std::os::raw::c_int)
+			.map_err(|e| Error::from_add_seals(e, || self.get_seals_raw()))
+	}
+
+	/// Check that the file has all of the `required` seals.
+	///
+	/// Fails with [`Error::MissingSeals`] if some of the seals are not present.
+	pub(crate) fn require_seals(&self, required: Seals) -> Result<Seals, Error> {
+		let seals = self.get_seals()?;
+		let missing = required - seals;
+		if missing.is_empty() {
+			Ok(seals)
+		} else {
+			Err(Error::MissingSeals(missing))
+		}
+	}
+
+	/// Diagnose an error from a write operation.
+	fn diagnose_write_error(&self, error: std::io::Error) -> std::io::Error {
+		#[cfg(target_os = "linux")]
+		let relevant = Seal::Write | Seal::FutureWrite | Seal::Grow;
+		#[cfg(not(target_os = "linux"))]
+		let relevant = Seal::Write | Seal::Grow;
+		Error::from_sealed(error, relevant, || self.get_seals_raw()).into()
 	}
 }
 
 impl From<MemFile> for OwnedFd {
 	fn from(value: MemFile) -> Self {
-		value.file.into()
+		value.into_fd()
 	}
 }
 
@@ -236,7 +423,7 @@ impl AsFd for MemFile {
 impl FromRawFd for MemFile {
 	unsafe fn from_raw_fd(fd: RawFd) -> Self {
 		let file = File::from_raw_fd(fd);
-		Self { file }
+		Self { file, backend: Backend::MemfdCreate, thp_mode: None, accounting: None }
 	}
 }
 
@@ -248,7 +435,7 @@ impl AsRawFd for MemFile {
 
 impl IntoRawFd for MemFile {
 	fn into_raw_fd(self) -> RawFd {
-		self.file.into_raw_fd()
+		self.into_untracked_file().into_raw_fd()
 	}
 }
 
@@ -258,7 +445,11 @@ impl std::os::unix::fs::FileExt for MemFile {
 	}
 
 	fn write_at(&self, buf: &[u8], offset: u64) -> std::io::Result<usize> {
-		self.file.write_at(buf, offset)
+		let len = self.limit_write(buf.len(), || Ok(offset))?;
+		let result = self.file.write_at(&buf[..len], offset)
+			.map_err(|e| self.diagnose_write_error(e));
+		self.update_accounting();
+		result
 	}
 }
 
@@ -268,7 +459,19 @@ impl std::io::Write for MemFile {
 	}
 
 	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-		self.file.write(buf)
+		let len = self.limit_write(buf.len(), || std::io::Seek::stream_position(&mut &self.file))?;
+		let result = self.file.write(&buf[..len])
+			.map_err(|e| self.diagnose_write_error(e));
+		self.update_accounting();
+		result
+	}
+
+	fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+		let len = self.limit_write(vectored::total_len(bufs), || std::io::Seek::stream_position(&mut &self.file))?;
+		let result = self.file.write_vectored(&vectored::truncate(bufs, len))
+			.map_err(|e| self.diagnose_write_error(e));
+		self.update_accounting();
+		result
 	}
 }
 
@@ -276,6 +479,12 @@ impl std::io::Read for MemFile {
 	fn read(&mut self, buf: &mut[u8]) -> std::io::Result<usize> {
 		self.file.read(buf)
 	}
+
+	fn read_vectored(&mut self, bufs: &mut [std::io::IoSliceMut<'_>]) -> std::io::Result<usize> {
+		self.file.read_vectored(bufs)
+	}
+
+	// TODO: Forward `read_buf()` once it is stable.
 }
 
 impl std::io::Seek for MemFile {
@@ -286,7 +495,7 @@ impl std::io::Seek for MemFile {
 
 impl From<MemFile> for std::process::Stdio {
 	fn from(other: MemFile) -> Self {
-		other.file.into()
+		other.into_untracked_file().into()
 	}
 }
 
@@ -298,6 +507,10 @@ impl From<MemFile> for std::process::Stdio {
 pub struct CreateOptions {
 	allow_sealing: bool,
 	huge_table: Option<HugeTlb>,
+	backend: Option<Backend>,
+	truncate_name: bool,
+	thp_mode: Option<ThpMode>,
+	max_size: Option<u64>,
 }
 
 impl CreateOptions {
@@ -316,7 +529,7 @@ impl CreateOptions {
 	///
 	/// This is a shorthand for [`MemFile::create`].
 	/// See that function for more details.
-	pub fn create(&self, name: &str) -> std::io::Result<MemFile> {
+	pub fn create(&self, name: &str) -> Result<MemFile, Error> {
 		MemFile::create(name, *self)
 	}
 
@@ -324,16 +537,37 @@ impl CreateOptions {
 	///
 	/// This is identical to [`Self::create`], except that it takes the name as [`CStr`] to avoid allocations.
 	/// See [`MemFile::create`] for more details.
-	pub fn create_cstr(&self, name: &CStr) -> std::io::Result<MemFile> {
+	pub fn create_cstr(&self, name: &CStr) -> Result<MemFile, Error> {
 		MemFile::create_cstr(name, *self)
 	}
 
+	/// Create a new [`MemFile`]` with the current options.
+	///
+	/// This is identical to [`Self::create`], except that it takes a pre-validated [`MemFileName`].
+	/// See [`MemFile::create`] for more details.
+	pub fn create_named(&self, name: &MemFileName<'_>) -> Result<MemFile, Error> {
+		MemFile::create_named(name, *self)
+	}
+
 	/// Allow sealing operations on the created [`MemFile`].
+	///
+	/// Only files created with `memfd_create` support sealing,
+	/// so this disables the fallback to other backends if `memfd_create` is not available.
 	pub fn allow_sealing(mut self, value: bool) -> Self {
 		self.allow_sealing = value;
 		self
 	}
 
+	/// Truncate names that are too long instead of failing to create the file.
+	///
+	/// By default, creating a file with a name longer than [`MemFileName::MAX_LEN`] bytes fails with [`Error::NameTooLong`].
+	/// When this option is enabled, the name is truncated instead.
+	/// Names that contain a null byte are always rejected.
+	pub fn truncate_name(mut self, value: bool) -> Self {
+		self.truncate_name = value;
+		self
+	}
+
 	/// Create the file in a `hugetlbfs` filesystem using huge pages for the translation look-aside buffer.
 	///
 	/// Support for this feature and specific sizes depend on the CPU and kernel configuration.
@@ -343,6 +577,47 @@ impl CreateOptions {
 		self
 	}
 
+	/// Request transparent huge pages for mappings of the file.
+	///
+	/// Unlike [`Self::huge_tlb()`], this does not require a pool of preallocated huge pages.
+	/// The mode is applied with `madvise` to every mapping of the file created by this crate.
+	/// Use [`GrowableMapping::huge_page_bytes()`] to check if huge pages are actually being used.
+	///
+	/// With [`ThpMode::Require`], creating the file fails with [`Error::TransparentHugePagesUnavailable`]
+	/// if the [system setting][ShmemThpSetting] never allows huge pages for shared memory.
+	/// This option can not be combined with [`Self::huge_tlb()`].
+	pub fn transparent_huge_pages(mut self, value: impl Into<Option<ThpMode>>) -> Self {
+		self.thp_mode = value.into();
+		self
+	}
+
+	/// Limit the size of the file to `value` bytes.
+	///
+	/// The limit is enforced by [`MemFile::set_len()`] and by all functions of [`MemFile`] that write data.
+	/// Resizing the file beyond the limit fails with [`Error::SizeLimitExceeded`].
+	/// Writes that cross the limit are shortened, and writes that start at or beyond the limit fail with the same error.
+	///
+	/// Once the file reaches the limit, it is sealed with [`Seal::Grow`] if sealing is allowed,
+	/// so that it can not grow beyond the limit through other file descriptors or processes either.
+	pub fn max_size(mut self, value: impl Into<Option<u64>>) -> Self {
+		self.max_size = value.into();
+		self
+	}
+
+	/// Force the use of a specific backend to create the file.
+	///
+	/// By default, files are created with `memfd_create`.
+	/// If that syscall is not available or denied, the file is created with a fallback backend instead,
+	/// unless sealing was allowed with [`Self::allow_sealing()`] or huge pages were requested with [`Self::huge_tlb()`].
+	///
+	/// Setting a backend disables the automatic fallback: creating the file fails if the chosen backend does not work.
+	/// Fallback backends do not support sealing or huge pages,
+	/// so forcing a fallback backend together with those options fails with an error of kind [`std::io::ErrorKind::InvalidInput`].
+	pub fn backend(mut self, value: impl Into<Option<Backend>>) -> Self {
+		self.backend = value.into();
+		self
+	}
+
 	/// Get the options as raw flags for `libc::memfd_create`.
 	fn as_flags(&self) -> std::os::raw::c_int {
 		let mut flags = sys::flags::MFD_CLOEXEC;
@@ -0,0 +1,235 @@
+//! Append-only logs stored in a [`MemFile`], which can be tailed by other processes.
+//!
+//! An [`AppendLog`] writes length-prefixed records to a [`MemFile`] and grows the file as needed.
+//! The file is sealed with [`Seal::Shrink`] when it is created,
+//! so a record that has been committed to the log can never disappear.
+//!
+//! Readers receive a copy of the file descriptor, for example using [`MemFile::try_clone()`] or the [`registry`][crate::registry],
+//! and use a [`LogReader`] to read new records as they are appended.
+//! The reader maps the file in memory using a [`GrowableMapping`], which is extended whenever the log has grown beyond the current mapping.
+//!
+//! # File format
+//! The file starts with a 16 byte header:
+//! * 8 bytes: the magic value `MEMFLOG1`.
+//! * 8 bytes: the committed length of the log, as atomic `u64` in native byte order.
+//!
+//! The header is followed by the records.
+//! Each record consists of the length of the record as `u32` in native byte order, followed by the record data.
+//! Only the records before the committed length are complete.
+//! Any data after the committed length must be ignored by readers.
+//!
+//! # Example
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use memfile::log::AppendLog;
+//!
+//! let mut log = AppendLog::create("events")?;
+//! let mut reader = log.reader()?;
+//! log.append(b"first")?;
+//! log.append(b"second")?;
+//!
+//! assert_eq!(reader.next()?, Some(&b"first"[..]));
+//! assert_eq!(reader.next()?, Some(&b"second"[..]));
+//! assert_eq!(reader.next()?, None);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::os::unix::fs::FileExt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::mapping::RawMapping;
+use crate::{CreateOptions, Error, GrowableMapping, MemFile, Seal};
+
+/// The magic value at the start of a log file.
+const MAGIC: [u8; 8] = *b"MEMFLOG1";
+
+/// The offset of the committed length in the header.
+const COMMITTED_OFFSET: usize = 8;
+
+/// The length of the header.
+const HEADER_LEN: u64 = 16;
+
+/// The length of the length prefix of a record.
+const PREFIX_LEN: u64 = 4;
+
+/// The initial size of a new log file.
+const INITIAL_CAPACITY: u64 = 4096;
+
+/// The writing side of an append-only log stored in a [`MemFile`].
+///
+/// See the [module documentation][self] for more information.
+#[derive(Debug)]
+pub struct AppendLog {
+	file: MemFile,
+	header: RawMapping,
+	end: u64,
+	capacity: u64,
+}
+
+impl AppendLog {
+	/// Create a new, empty log.
+	///
+	/// The `name` is used as name for the [`MemFile`].
+	/// The file is created with sealing support and sealed with [`Seal::Shrink`].
+	pub fn create(name: &str) -> Result<Self, Error> {
+		let file = MemFile::create(name, CreateOptions::new().allow_sealing(true))?;
+		file.set_len(INITIAL_CAPACITY)?;
+		file.write_all_at(&MAGIC, 0)?;
+		file.add_seal(Seal::Shrink)?;
+
+		let header = RawMapping::new(file.as_fd(), HEADER_LEN as usize, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED)?;
+		let log = Self {
+			file,
+			header,
+			end: HEADER_LEN,
+			capacity: INITIAL_CAPACITY,
+		};
+		log.committed().store(HEADER_LEN, Ordering::Release);
+		Ok(log)
+	}
+
+	/// Append a record to the log.
+	///
+	/// The file is grown if there is not enough space for the record.
+	/// The record becomes visible to readers once it has been written completely.
+	///
+	/// Returns the offset of the record in the file.
+	/// Records can not be longer than [`u32::MAX`] bytes.
+	pub fn append(&mut self, record: &[u8]) -> std::io::Result<u64> {
+		let len = u32::try_from(record.len())
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "record is too long for the log"))?;
+		let offset = self.end;
+		let end = offset
+			.checked_add(PREFIX_LEN + u64::from(len))
+			.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "log would exceed the maximum file size"))?;
+
+		if end > self.capacity {
+			let capacity = end.max(self.capacity.saturating_mul(2));
+			self.file.set_len(capacity)?;
+			self.capacity = capacity;
+		}
+
+		self.file.write_all_at(&len.to_ne_bytes(), offset)?;
+		self.file.write_all_at(record, offset + PREFIX_LEN)?;
+		self.end = end;
+		self.committed().store(end, Ordering::Release);
+		Ok(offset)
+	}
+
+	/// Get the committed length of the log in bytes, including the header.
+	pub fn committed_len(&self) -> u64 {
+		self.end
+	}
+
+	/// Get the [`MemFile`] that holds the log.
+	///
+	/// You can use this to share the log with other processes.
+	/// You should not write to the file or change its size directly.
+	pub fn file(&self) -> &MemFile {
+		&self.file
+	}
+
+	/// Create a reader for the log that starts at the first record.
+	///
+	/// This duplicates the file descriptor of the log with [`MemFile::try_clone()`].
+	pub fn reader(&self) -> std::io::Result<LogReader> {
+		LogReader::new(self.file.try_clone()?)
+	}
+
+	/// Get the committed length in the header of the log.
+	fn committed(&self) -> &AtomicU64 {
+		// The header mapping is page aligned, so the committed length is properly aligned.
+		unsafe { &*self.header.as_ptr().add(COMMITTED_OFFSET).cast::<AtomicU64>() }
+	}
+}
+
+/// The reading side of an append-only log stored in a [`MemFile`].
+///
+/// See the [module documentation][self] for more information.
+#[derive(Debug)]
+pub struct LogReader {
+	mapping: GrowableMapping,
+	position: u64,
+	record: Vec<u8>,
+}
+
+impl LogReader {
+	/// Create a reader for a log file, starting at the first record.
+	///
+	/// The file must be sealed with [`Seal::Shrink`], otherwise the records could be removed while they are being read.
+	/// If the seal is missing, an error of kind [`std::io::ErrorKind::InvalidInput`] is returned.
+	/// If the file does not start with a valid log header, an error of kind [`std::io::ErrorKind::InvalidData`] is returned.
+	pub fn new(file: MemFile) -> std::io::Result<Self> {
+		let mapping = GrowableMapping::new(file)?;
+		let mut magic = [0; MAGIC.len()];
+		if mapping.len() < HEADER_LEN as usize || mapping.read_at(&mut magic, 0) != MAGIC.len() || magic != MAGIC {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "file does not contain a valid log header"));
+		}
+		Ok(Self {
+			mapping,
+			position: HEADER_LEN,
+			record: Vec::new(),
+		})
+	}
+
+	/// Read the next record from the log.
+	///
+	/// Returns `None` if there are no new records yet.
+	/// You can call this function again later to check for new records.
+	///
+	/// The record is copied out of the shared memory, so the writer can not modify it while you are using it.
+	///
+	/// If the log has grown beyond the current memory mapping, the mapping is extended with [`GrowableMapping::refresh()`].
+	/// If the log contains invalid data, an error of kind [`std::io::ErrorKind::InvalidData`] is returned.
+	#[allow(clippy::should_implement_trait)]
+	pub fn next(&mut self) -> std::io::Result<Option<&[u8]>> {
+		let committed = self.committed();
+		if committed < self.position {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "committed length of the log decreased"));
+		}
+		if committed == self.position {
+			return Ok(None);
+		}
+		if committed > self.mapping.len() as u64 {
+			self.mapping.refresh()?;
+			if committed > self.mapping.len() as u64 {
+				return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "committed length of the log exceeds the file size"));
+			}
+		}
+
+		if self.position + PREFIX_LEN > committed {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "record length extends beyond the committed length of the log"));
+		}
+		let start = self.position as usize;
+		let mut len = [0; PREFIX_LEN as usize];
+		self.mapping.read_at(&mut len, start);
+		let len = u32::from_ne_bytes(len);
+		let end = self.position + PREFIX_LEN + u64::from(len);
+		if end > committed {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "record extends beyond the committed length of the log"));
+		}
+
+		self.record.resize(len as usize, 0);
+		self.mapping.read_at(&mut self.record, start + PREFIX_LEN as usize);
+		self.position = end;
+		Ok(Some(&self.record))
+	}
+
+	/// Get the offset in the file of the next record to read.
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+
+	/// Get the [`MemFile`] that holds the log.
+	pub fn file(&self) -> &MemFile {
+		self.mapping.file()
+	}
+
+	/// Get the committed length from the header of the log.
+	fn committed(&self) -> u64 {
+		// The mapping is page aligned, so the committed length is properly aligned.
+		let committed = unsafe { &*self.mapping.as_ptr().add(COMMITTED_OFFSET).cast::<AtomicU64>() };
+		committed.load(Ordering::Acquire)
+	}
+}
@@ -0,0 +1,316 @@
+use std::os::fd::{AsRawFd, BorrowedFd};
+use std::os::raw::c_int;
+use std::ptr::NonNull;
+
+use crate::{sys, Error, MemFile, Seal};
+
+/// A memory mapping that is unmapped when dropped.
+///
+/// This type does not give access to the mapped memory by itself.
+/// It is up to the owner to decide how the memory may be accessed.
+pub(crate) struct RawMapping {
+	data: NonNull<u8>,
+	len: usize,
+}
+
+// The mapping is just a region of memory, it has no thread affinity.
+unsafe impl Send for RawMapping {}
+unsafe impl Sync for RawMapping {}
+
+impl RawMapping {
+	/// Map `len` bytes of a file, starting at the start of the file.
+	///
+	/// If `len` is zero, no mapping is created and a dangling pointer is used instead.
+	pub fn new(fd: BorrowedFd, len: usize, prot: c_int, flags: c_int) -> std::io::Result<Self> {
+		if len == 0 {
+			return Ok(Self::empty());
+		}
+		let data = sys::mmap(fd.as_raw_fd(), len, prot, flags, 0)?;
+		Ok(Self {
+			data: NonNull::new(data.cast()).unwrap(),
+			len,
+		})
+	}
+
+	/// Create an empty mapping without calling `mmap`.
+	pub fn empty() -> Self {
+		Self {
+			data: NonNull::dangling(),
+			len: 0,
+		}
+	}
+
+	/// Change the length of the mapping.
+	///
+	/// On Linux and Android, this uses `mremap`, which may move the mapping to a different address.
+	/// On other platforms, the file is mapped again and the old mapping is removed.
+	/// The `prot` and `flags` arguments must be the same as those used to create the mapping.
+	pub fn remap(&mut self, fd: BorrowedFd, len: usize, prot: c_int, flags: c_int) -> std::io::Result<()> {
+		#[cfg(any(target_os = "linux", target_os = "android"))]
+		if self.len != 0 && len != 0 {
+			let data = unsafe { sys::mremap(self.data.as_ptr().cast(), self.len, len)? };
+			self.data = NonNull::new(data.cast()).unwrap();
+			self.len = len;
+			return Ok(());
+		}
+
+		*self = Self::new(fd, len, prot, flags)?;
+		Ok(())
+	}
+
+	/// Get a pointer to the start of the mapping.
+	pub fn as_ptr(&self) -> *mut u8 {
+		self.data.as_ptr()
+	}
+
+	/// Get the length of the mapping in bytes.
+	pub fn len(&self) -> usize {
+		self.len
+	}
+}
+
+impl Drop for RawMapping {
+	fn drop(&mut self) {
+		if self.len != 0 {
+			unsafe {
+				let _ = sys::munmap(self.data.as_ptr().cast(), self.len);
+			}
+		}
+	}
+}
+
+impl std::fmt::Debug for RawMapping {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("RawMapping")
+			.field("data", &self.data)
+			.field("len", &self.len)
+			.finish()
+	}
+}
+
+/// A read-only, shared memory mapping of a [`MemFile`] that can follow the file as it grows.
+///
+/// When another process grows a shared file, existing memory mappings do not cover the new region.
+/// Use [`Self::refresh()`] to check the current size of the file and extend the mapping if needed.
+///
+/// Refreshing the mapping may move it to a different address.
+/// Because [`Self::refresh()`] takes `&mut self`, the borrow checker prevents you from holding on to slices of the mapping across a refresh.
+/// If you store raw pointers or offsets derived from the mapping,
+/// you can use [`Self::generation()`] to detect that the mapping has been moved since they were created.
+///
+/// The mapping can only be created for files that are sealed with [`Seal::Shrink`],
+/// so growing is the only possible change in size and the mapped memory remains valid.
+///
+/// The contents of the file can still be modified by other processes, unless it is also sealed with [`Seal::Write`].
+/// Therefore, [`Self::as_slice()`] only gives access to the mapped memory as a slice if the file has that seal.
+/// Otherwise, use [`Self::read_at()`] to copy data out of the mapping, or [`Self::as_ptr()`] to access it through a raw pointer.
+#[derive(Debug)]
+pub struct GrowableMapping {
+	file: MemFile,
+	mapping: RawMapping,
+	generation: u64,
+}
+
+impl GrowableMapping {
+	/// Map the whole file in memory.
+	///
+	/// This function fails with [`Error::MissingSeals`] if the file is not sealed with [`Seal::Shrink`].
+	pub fn new(file: MemFile) -> Result<Self, Error> {
+		file.require_seals(Seal::Shrink.into())?;
+		let mut mapping = Self {
+			file,
+			mapping: RawMapping::empty(),
+			generation: 0,
+		};
+		mapping.refresh()?;
+		mapping.generation = 0;
+		Ok(mapping)
+	}
+
+	/// Extend the mapping if the file has grown.
+	///
+	/// The current size of the file is retrieved using [`MemFile::metadata()`].
+	/// If the file was created with [`CreateOptions::transparent_huge_pages()`][crate::CreateOptions::transparent_huge_pages],
+	/// the mode is applied to the extended mapping.
+	/// On Linux and Android, the mapping is extended using `mremap`.
+	/// On other platforms, the file is mapped again and the old mapping is removed.
+	///
+	/// Returns `true` if the mapping was changed, in which case the [generation][Self::generation] is incremented.
+	pub fn refresh(&mut self) -> std::io::Result<bool> {
+		let len = usize::try_from(self.file.metadata()?.len())
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "file is too large to map in memory"))?;
+		if len <= self.mapping.len() {
+			return Ok(false);
+		}
+		self.mapping.remap(self.file.as_fd(), len, libc::PROT_READ, libc::MAP_SHARED)?;
+		self.generation += 1;
+		self.file.apply_thp_mode(&self.mapping)?;
+		Ok(true)
+	}
+
+	/// Get the generation of the mapping.
+	///
+	/// The generation starts at zero, and it is incremented every time the mapping is changed by [`Self::refresh()`].
+	pub fn generation(&self) -> u64 {
+		self.generation
+	}
+
+	/// Get the length of the mapping in bytes.
+	///
+	/// This is the size of the file when the mapping was last refreshed.
+	pub fn len(&self) -> usize {
+		self.mapping.len()
+	}
+
+	/// Check if the mapping is empty.
+	pub fn is_empty(&self) -> bool {
+		self.mapping.len() == 0
+	}
+
+	/// Get a pointer to the start of the mapping.
+	///
+	/// The pointer is invalidated when the mapping is moved by [`Self::refresh()`].
+	pub fn as_ptr(&self) -> *const u8 {
+		self.mapping.as_ptr()
+	}
+
+	/// Copy data from the mapping at the given offset into `buffer`.
+	///
+	/// Returns the number of bytes copied, which is less than the size of `buffer` if the end of the mapping is reached.
+	/// If other processes modify the data while it is being copied, the copy may contain a mix of old and new data.
+	pub fn read_at(&self, buffer: &mut [u8], offset: usize) -> usize {
+		let len = buffer.len().min(self.mapping.len().saturating_sub(offset));
+		if len > 0 {
+			// The file is sealed against shrinking, so the whole mapping stays valid.
+			unsafe { std::ptr::copy_nonoverlapping(self.mapping.as_ptr().add(offset), buffer.as_mut_ptr(), len) };
+		}
+		len
+	}
+
+	/// Get the mapped memory as a slice.
+	///
+	/// This function fails with [`Error::MissingSeals`] if the file is not sealed with [`Seal::Write`],
+	/// since the memory could then be modified while it is borrowed.
+	/// Note that the seal must be added before the file is mapped: the kernel does not allow it while shared mappings of the file exist.
+	pub fn as_slice(&self) -> Result<&[u8], Error> {
+		self.file.require_seals(Seal::Write.into())?;
+		Ok(unsafe { self.as_slice_unchecked() })
+	}
+
+	/// Get the mapped memory as a slice, without checking that the file is sealed with [`Seal::Write`].
+	///
+	/// # Safety
+	/// The mapped memory must not be modified by any process while the returned slice is alive.
+	pub unsafe fn as_slice_unchecked(&self) -> &[u8] {
+		// The file is sealed against shrinking, so the whole mapping stays valid.
+		std::slice::from_raw_parts(self.mapping.as_ptr(), self.mapping.len())
+	}
+
+	/// Get the [`MemFile`] that is mapped.
+	pub fn file(&self) -> &MemFile {
+		&self.file
+	}
+
+	/// Remove the mapping and return the [`MemFile`].
+	pub fn into_file(self) -> MemFile {
+		self.file
+	}
+
+	/// Get the underlying mapping.
+	pub(crate) fn raw(&self) -> &RawMapping {
+		&self.mapping
+	}
+}
+
+/// A private, copy-on-write memory mapping of a [`MemFile`].
+///
+/// The mapping is created with `MAP_PRIVATE`, so modifications are only visible to this mapping and are never written back to the file.
+/// Pages are copied lazily when they are first written to, so creating the mapping does not copy the file contents up front.
+///
+/// Pages that have not been modified yet still reflect the contents of the file,
+/// so the mapping can only be created for files that are sealed with [`Seal::Write`].
+/// Otherwise, the contents could change while they are borrowed.
+/// Use [`MemFile::snapshot()`] to get a sealed copy of a file that is still being modified.
+///
+/// The mapping remains valid after the [`MemFile`] is closed.
+/// It is created with [`MemFile::map_private()`].
+#[derive(Debug)]
+pub struct PrivateMapping {
+	mapping: RawMapping,
+}
+
+impl PrivateMapping {
+	/// Get the length of the mapping in bytes.
+	pub fn len(&self) -> usize {
+		self.mapping.len()
+	}
+
+	/// Check if the mapping is empty.
+	pub fn is_empty(&self) -> bool {
+		self.mapping.len() == 0
+	}
+
+	/// Get the mapped memory as a slice.
+	pub fn as_slice(&self) -> &[u8] {
+		// The file is sealed against shrinking and writing, so the whole mapping stays valid and nobody else can modify it.
+		unsafe { std::slice::from_raw_parts(self.mapping.as_ptr(), self.mapping.len()) }
+	}
+
+	/// Get the mapped memory as a mutable slice.
+	///
+	/// Writing to the slice only modifies the private copy of the data.
+	pub fn as_mut_slice(&mut self) -> &mut [u8] {
+		// The file is sealed against shrinking and writing, so the whole mapping stays valid and nobody else can modify it.
+		unsafe { std::slice::from_raw_parts_mut(self.mapping.as_ptr(), self.mapping.len()) }
+	}
+
+	/// Get the underlying mapping.
+	pub(crate) fn raw(&self) -> &RawMapping {
+		&self.mapping
+	}
+}
+
+impl std::ops::Deref for PrivateMapping {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		self.as_slice()
+	}
+}
+
+impl std::ops::DerefMut for PrivateMapping {
+	fn deref_mut(&mut self) -> &mut [u8] {
+		self.as_mut_slice()
+	}
+}
+
+impl AsRef<[u8]> for PrivateMapping {
+	fn as_ref(&self) -> &[u8] {
+		self.as_slice()
+	}
+}
+
+impl AsMut<[u8]> for PrivateMapping {
+	fn as_mut(&mut self) -> &mut [u8] {
+		self.as_mut_slice()
+	}
+}
+
+impl MemFile {
+	/// Create a private, copy-on-write mapping of the whole file.
+	///
+	/// The returned mapping can be modified without affecting the file or other mappings of the file.
+	/// See [`PrivateMapping`] for more details.
+	///
+	/// This function fails with [`Error::MissingSeals`] if the file is not sealed with [`Seal::Shrink`] and [`Seal::Write`].
+	/// Accessing the mapping after the file has been shrunk would raise a `SIGBUS` signal,
+	/// and pages of the mapping that have not been modified would change when the file is modified.
+	pub fn map_private(&self) -> Result<PrivateMapping, Error> {
+		self.require_seals(Seal::Shrink | Seal::Write)?;
+		let len = usize::try_from(self.metadata()?.len())
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "file is too large to map in memory"))?;
+		let mapping = RawMapping::new(self.as_fd(), len, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_PRIVATE)?;
+		self.apply_thp_mode(&mapping)?;
+		Ok(PrivateMapping { mapping })
+	}
+}
@@ -0,0 +1,114 @@
+use std::borrow::Cow;
+use std::ffi::{CStr, CString};
+
+use crate::Error;
+
+/// A validated name for a [`MemFile`][crate::MemFile].
+///
+/// The kernel only accepts names of up to [`MemFileName::MAX_LEN`] bytes without null bytes.
+/// Validating the name up front lets you catch invalid names when they are generated,
+/// rather than getting an unhelpful error from the kernel when the file is created.
+///
+/// Names can be validated at compile time with [`MemFileName::from_static()`]:
+/// ```
+/// use memfile::MemFileName;
+/// const NAME: MemFileName = MemFileName::from_static(c"frame-buffer");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MemFileName<'a> {
+	name: Cow<'a, CStr>,
+}
+
+impl MemFileName<'static> {
+	/// The maximum length of a name in bytes, excluding the terminating null byte.
+	pub const MAX_LEN: usize = 249;
+
+	/// Create a name from a static C string.
+	///
+	/// This function can be used in a `const` context to validate a name at compile time.
+	///
+	/// # Panics
+	/// This function panics if the name is longer than [`Self::MAX_LEN`] bytes.
+	/// In a `const` context, this results in a compile time error instead.
+	pub const fn from_static(name: &'static CStr) -> Self {
+		if name.to_bytes().len() > Self::MAX_LEN {
+			panic!("memfd name exceeds the maximum length of 249 bytes");
+		}
+		Self { name: Cow::Borrowed(name) }
+	}
+
+	/// Create a name from a string.
+	///
+	/// This function fails with [`Error::NameContainsNul`] if the name contains a null byte,
+	/// or with [`Error::NameTooLong`] if the name is longer than [`Self::MAX_LEN`] bytes.
+	pub fn new(name: &str) -> Result<Self, Error> {
+		if name.len() > Self::MAX_LEN {
+			return Err(Error::NameTooLong);
+		}
+		let name = CString::new(name).map_err(|_| Error::NameContainsNul)?;
+		Ok(Self { name: Cow::Owned(name) })
+	}
+
+	/// Create a name from a string, truncating it if it is too long.
+	///
+	/// If the name is longer than [`Self::MAX_LEN`] bytes, it is truncated at the last character boundary that fits.
+	///
+	/// This function fails with [`Error::NameContainsNul`] if the name contains a null byte.
+	pub fn new_truncated(name: &str) -> Result<Self, Error> {
+		let mut len = name.len().min(Self::MAX_LEN);
+		while !name.is_char_boundary(len) {
+			len -= 1;
+		}
+		Self::new(&name[..len])
+	}
+}
+
+impl<'a> MemFileName<'a> {
+	/// Create a name from a C string without allocating.
+	///
+	/// This function fails with [`Error::NameTooLong`] if the name is longer than [`MemFileName::MAX_LEN`] bytes.
+	pub fn from_cstr(name: &'a CStr) -> Result<Self, Error> {
+		if name.to_bytes().len() > MemFileName::MAX_LEN {
+			Err(Error::NameTooLong)
+		} else {
+			Ok(Self { name: Cow::Borrowed(name) })
+		}
+	}
+
+	/// Create a name from a C string, truncating it if it is too long.
+	///
+	/// The name is only copied if it needs to be truncated.
+	/// Since a C string is not necessarily valid UTF-8, it is truncated at exactly [`MemFileName::MAX_LEN`] bytes.
+	pub fn from_cstr_truncated(name: &'a CStr) -> Self {
+		let bytes = name.to_bytes();
+		if bytes.len() <= MemFileName::MAX_LEN {
+			Self { name: Cow::Borrowed(name) }
+		} else {
+			// The bytes come from a CStr, so they can not contain a null byte.
+			let name = CString::new(&bytes[..MemFileName::MAX_LEN]).unwrap();
+			Self { name: Cow::Owned(name) }
+		}
+	}
+
+	/// Get the name as C string.
+	pub fn as_cstr(&self) -> &CStr {
+		&self.name
+	}
+
+	/// Convert the name into an owned name that does not borrow any data.
+	pub fn into_owned(self) -> MemFileName<'static> {
+		MemFileName { name: Cow::Owned(self.name.into_owned()) }
+	}
+}
+
+impl AsRef<CStr> for MemFileName<'_> {
+	fn as_ref(&self) -> &CStr {
+		self.as_cstr()
+	}
+}
+
+impl std::fmt::Display for MemFileName<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{}", self.name.to_string_lossy())
+	}
+}
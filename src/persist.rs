@@ -0,0 +1,194 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{CreateOptions, MemFile, MemFileName, Seal, Seals};
+
+impl MemFile {
+	/// Save the contents of the file to disk.
+	///
+	/// The contents are first written to a temporary file in the same directory, which is then renamed to `path`.
+	/// This ensures that `path` always refers to either the old file or the complete new file, even if the process crashes.
+	///
+	/// The seals of the file are not saved.
+	/// If a seals file for `path` already exists, it is removed after the contents have been saved,
+	/// so that [`Self::load_from`] does not apply outdated seals.
+	/// If the process crashes before the seals file is removed, it may remain next to the new contents.
+	/// Use [`Self::save_to_with_seals`] if you want to save the seals too.
+	///
+	/// The file position of the [`MemFile`] is not changed.
+	pub fn save_to(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+		let path = path.as_ref();
+		self.save_contents(path)?;
+		remove_if_exists(&seals_path(path))
+	}
+
+	/// Save the contents and the seals of the file to disk.
+	///
+	/// This is identical to [`Self::save_to`], except that the active seals are also saved to a separate file.
+	/// The seals file has the same path as the file contents, with `.seals` appended.
+	/// It contains the names of the seals, one per line.
+	///
+	/// The seals file is replaced atomically after the contents have been saved.
+	/// If the process crashes in between, the new contents may be accompanied by the previous seals file, or by none.
+	///
+	/// When the file is loaded again with [`Self::load_from`], the seals are re-applied.
+	pub fn save_to_with_seals(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+		let path = path.as_ref();
+		let seals = self.get_seals()?;
+		self.save_contents(path)?;
+		write_atomic(&seals_path(path), |file| {
+			let mut file = std::io::BufWriter::new(file);
+			for seal in seals {
+				writeln!(file, "{}", seal_name(seal))?;
+			}
+			file.flush()
+		})
+	}
+
+	/// Load a file from disk into a new [`MemFile`].
+	///
+	/// The new [`MemFile`] is created with the given options,
+	/// and it is named after the final component of `path`, truncated if necessary.
+	///
+	/// If the file on disk is larger than the [maximum size][CreateOptions::max_size] in the options,
+	/// an error of kind [`std::io::ErrorKind::FileTooLarge`] is returned.
+	///
+	/// If a seals file exists for `path`, the recorded seals are applied after the contents have been loaded.
+	/// In that case, sealing is always enabled for the new file, regardless of the given options.
+	/// See [`Self::save_to_with_seals`] for more information on the seals file.
+	///
+	/// The file position of the returned [`MemFile`] is at the start of the file.
+	pub fn load_from(path: impl AsRef<Path>, options: CreateOptions) -> std::io::Result<Self> {
+		let path = path.as_ref();
+		let seals = read_seals(&seals_path(path))?;
+		let options = if seals.is_empty() {
+			options
+		} else {
+			options.allow_sealing(true)
+		};
+
+		let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+		let name = MemFileName::new_truncated(&name)?;
+		let memfile = MemFile::create_named(&name, options)?;
+
+		let source = File::open(path)?;
+		let len = source.metadata()?.len();
+		if let Some(max_size) = memfile.max_size().filter(|&max_size| len > max_size) {
+			return Err(crate::Error::SizeLimitExceeded(max_size).into());
+		}
+		let copied = memfile.copy_from_fd(&source, 0..len)?;
+		if copied != len {
+			return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "file was truncated while loading it"));
+		}
+
+		if !seals.is_empty() {
+			memfile.add_seals(seals)?;
+		}
+		Ok(memfile)
+	}
+
+	/// Atomically write the contents of the file to disk.
+	fn save_contents(&self, path: &Path) -> std::io::Result<()> {
+		let len = self.metadata()?.len();
+		write_atomic(path, |file| {
+			let copied = self.copy_to_fd(&*file, 0..len)?;
+			if copied != len {
+				return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "file was truncated while saving it"));
+			}
+			Ok(())
+		})
+	}
+}
+
+/// Get the path of the seals file for a saved [`MemFile`].
+fn seals_path(path: &Path) -> PathBuf {
+	let mut seals_path = OsString::from(path);
+	seals_path.push(".seals");
+	seals_path.into()
+}
+
+/// Atomically create or replace a file by writing to a temporary file first.
+fn write_atomic(path: &Path, write: impl FnOnce(&mut File) -> std::io::Result<()>) -> std::io::Result<()> {
+	static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+	let file_name = path.file_name()
+		.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "path does not have a file name"))?;
+	let mut temp_name = OsString::from(".");
+	temp_name.push(file_name);
+	temp_name.push(format!(".{}.{}.tmp", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+	let temp_path = path.with_file_name(temp_name);
+
+	let mut file = std::fs::OpenOptions::new()
+		.write(true)
+		.create_new(true)
+		.open(&temp_path)?;
+
+	let result = write(&mut file)
+		.and_then(|()| file.sync_all())
+		.and_then(|()| std::fs::rename(&temp_path, path));
+	if let Err(e) = result {
+		let _ = std::fs::remove_file(&temp_path);
+		return Err(e);
+	}
+
+	// Sync the directory to make the rename itself durable.
+	let parent = match path.parent() {
+		Some(parent) if !parent.as_os_str().is_empty() => parent,
+		_ => Path::new("."),
+	};
+	File::open(parent)?.sync_all()
+}
+
+/// Remove a file, ignoring the error if it does not exist.
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+	match std::fs::remove_file(path) {
+		Ok(()) => Ok(()),
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+		Err(e) => Err(e),
+	}
+}
+
+/// Read the seals from a seals file.
+///
+/// If the file does not exist, an empty set of seals is returned.
+fn read_seals(path: &Path) -> std::io::Result<Seals> {
+	let file = match File::open(path) {
+		Ok(x) => x,
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Seals::empty()),
+		Err(e) => return Err(e),
+	};
+
+	let mut seals = Seals::empty();
+	for line in std::io::BufReader::new(file).lines() {
+		let line = line?;
+		let line = line.trim();
+		if line.is_empty() {
+			continue;
+		}
+		let seal = parse_seal(line)
+			.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("unknown seal in {}: {line:?}", path.display())))?;
+		seals |= seal;
+	}
+	Ok(seals)
+}
+
+/// Get the name of a seal as used in the seals file.
+fn seal_name(seal: Seal) -> &'static str {
+	match seal {
+		Seal::Seal => "Seal",
+		Seal::Shrink => "Shrink",
+		Seal::Grow => "Grow",
+		Seal::Write => "Write",
+		#[cfg(target_os = "linux")]
+		Seal::FutureWrite => "FutureWrite",
+	}
+}
+
+/// Parse the name of a seal from a seals file.
+fn parse_seal(name: &str) -> Option<Seal> {
+	crate::seal::ALL_SEALS.into_iter()
+		.find(|&seal| seal_name(seal) == name)
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::io::Seek;
+use std::os::unix::fs::MetadataExt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{sys, CreateOptions, Error, MemFile, MemFileName, Seal, Seals};
+
+/// The smallest size class of a [`MemFilePool`].
+const MIN_SIZE_CLASS: u64 = 4096;
+
+/// A pool of reusable [`MemFile`]s to avoid the cost of creating and closing files.
+///
+/// Files are grouped by size class: the file size rounded up to a power of two, with a minimum of 4 KiB.
+/// If the options of the pool have a [maximum size][CreateOptions::max_size], the size classes are capped at that size.
+/// When a file is returned to the pool with [`MemFilePool::put()`], it is truncated to release its storage,
+/// so idle files in the pool do not consume memory.
+///
+/// Seals can not be removed from a file, so sealed files are never recycled.
+/// Only files that have exactly the same seals as a newly created file are accepted back into the pool.
+/// Note that files with a maximum size are sealed against growing when they reach that size, if sealing is allowed.
+///
+/// Files that were created with different options than the pool are not accepted either.
+/// The pool checks if sealing is allowed, the maximum size, the transparent huge page mode, the backend and whether huge pages are used.
+/// The name of the file and the size of the huge pages are not checked.
+///
+/// You must make sure that no memory mappings of a file exist when you return it to the pool,
+/// since the file will be handed out again and modified by a new user.
+#[derive(Debug)]
+pub struct MemFilePool {
+	name: MemFileName<'static>,
+	options: CreateOptions,
+	max_idle_per_class: usize,
+	idle: Mutex<HashMap<u64, Vec<MemFile>>>,
+	hits: AtomicU64,
+	misses: AtomicU64,
+	recycled: AtomicU64,
+	rejected: AtomicU64,
+}
+
+impl MemFilePool {
+	/// Create a new pool.
+	///
+	/// All files created by the pool use the given name and options.
+	pub fn new(name: &str, options: CreateOptions) -> Result<Self, Error> {
+		Ok(Self {
+			name: MemFileName::new(name)?,
+			options,
+			max_idle_per_class: 16,
+			idle: Default::default(),
+			hits: AtomicU64::new(0),
+			misses: AtomicU64::new(0),
+			recycled: AtomicU64::new(0),
+			rejected: AtomicU64::new(0),
+		})
+	}
+
+	/// Set the maximum number of idle files to keep for each size class.
+	///
+	/// Files returned to the pool when the limit is reached are closed.
+	/// The default limit is 16 files per size class.
+	pub fn max_idle_per_class(mut self, value: usize) -> Self {
+		self.max_idle_per_class = value;
+		self
+	}
+
+	/// Get a file of the given size from the pool.
+	///
+	/// If the pool has an idle file in the right size class, it is reused.
+	/// Otherwise, a new file is created.
+	///
+	/// The returned file has exactly the requested size, is filled with zeroes and has its file position at the start.
+	pub fn get(&self, size: u64) -> Result<MemFile, Error> {
+		let class = self.size_class(size);
+		let file = self.idle.lock().unwrap()
+			.get_mut(&class)
+			.and_then(|files| files.pop());
+
+		let file = match file {
+			Some(file) => {
+				self.hits.fetch_add(1, Ordering::Relaxed);
+				file
+			},
+			None => {
+				self.misses.fetch_add(1, Ordering::Relaxed);
+				MemFile::create_named(&self.name, self.options)?
+			},
+		};
+		file.set_len(size)?;
+		Ok(file)
+	}
+
+	/// Return a file to the pool so it can be reused.
+	///
+	/// The file is rejected if it has been sealed, if it was created with different options than the pool,
+	/// if releasing its storage fails, or if the pool already has the maximum number of idle files for the size class.
+	/// Rejected files are closed.
+	///
+	/// Returns `true` if the file was added to the pool.
+	pub fn put(&self, mut file: MemFile) -> bool {
+		let recycled = match self.prepare_for_reuse(&mut file) {
+			Some(class) => {
+				let mut idle = self.idle.lock().unwrap();
+				let files = idle.entry(class).or_default();
+				if files.len() < self.max_idle_per_class {
+					files.push(file);
+					true
+				} else {
+					false
+				}
+			},
+			None => false,
+		};
+
+		if recycled {
+			self.recycled.fetch_add(1, Ordering::Relaxed);
+		} else {
+			self.rejected.fetch_add(1, Ordering::Relaxed);
+		}
+		recycled
+	}
+
+	/// Get statistics about the pool.
+	pub fn stats(&self) -> PoolStats {
+		PoolStats {
+			hits: self.hits.load(Ordering::Relaxed),
+			misses: self.misses.load(Ordering::Relaxed),
+			recycled: self.recycled.load(Ordering::Relaxed),
+			rejected: self.rejected.load(Ordering::Relaxed),
+			idle: self.idle.lock().unwrap().values().map(|files| files.len()).sum(),
+		}
+	}
+
+	/// Check if a file can be reused, and release its storage.
+	///
+	/// Returns the size class of the file, or `None` if it can not be reused.
+	fn prepare_for_reuse(&self, file: &mut MemFile) -> Option<u64> {
+		let metadata = file.metadata().ok()?;
+		if !self.matches_options(file, &metadata) {
+			return None;
+		}
+
+		let class = self.size_class(metadata.len());
+		file.set_len(0).ok()?;
+		file.rewind().ok()?;
+		Some(class)
+	}
+
+	/// Check if a file looks like it was created with the options of the pool and has not been sealed since.
+	fn matches_options(&self, file: &MemFile, metadata: &std::fs::Metadata) -> bool {
+		// A new file created without sealing support has the `Seal` seal.
+		let fresh_seals = if self.options.allow_sealing {
+			Seals::empty()
+		} else {
+			Seals::from(Seal::Seal)
+		};
+		// Files in a hugetlbfs report the huge page size as block size.
+		let huge_pages = metadata.blksize() > sys::page_size() as u64;
+
+		file.get_seals().is_ok_and(|seals| seals == fresh_seals)
+			&& file.max_size() == self.options.max_size
+			&& file.transparent_huge_pages() == self.options.thp_mode
+			&& self.options.backend.is_none_or(|backend| backend == file.backend())
+			&& huge_pages == self.options.huge_table.is_some()
+	}
+
+	/// Get the size class for a file size, capped at the maximum size of the files in the pool.
+	fn size_class(&self, size: u64) -> u64 {
+		let class = size_class(size);
+		self.options.max_size.map_or(class, |max_size| class.min(max_size))
+	}
+}
+
+/// Statistics about a [`MemFilePool`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+	hits: u64,
+	misses: u64,
+	recycled: u64,
+	rejected: u64,
+	idle: usize,
+}
+
+impl PoolStats {
+	/// The number of requests that were served with an idle file from the pool.
+	pub fn hits(&self) -> u64 {
+		self.hits
+	}
+
+	/// The number of requests that required a new file to be created.
+	pub fn misses(&self) -> u64 {
+		self.misses
+	}
+
+	/// The number of files that were returned to the pool and kept for reuse.
+	pub fn recycled(&self) -> u64 {
+		self.recycled
+	}
+
+	/// The number of files that were returned to the pool but closed instead of kept.
+	pub fn rejected(&self) -> u64 {
+		self.rejected
+	}
+
+	/// The number of idle files currently in the pool.
+	pub fn idle(&self) -> usize {
+		self.idle
+	}
+}
+
+/// Get the size class for a file size.
+fn size_class(size: u64) -> u64 {
+	size.max(MIN_SIZE_CLASS).checked_next_power_of_two().unwrap_or(size)
+}
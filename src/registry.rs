@@ -0,0 +1,302 @@
+//! Registry for discovering [`MemFile`]s across processes.
+//!
+//! A `memfd` is anonymous: other processes can only access it if they receive the file descriptor.
+//! This module provides a small registry service that runs on a Unix socket.
+//! Processes can [publish][Client::publish] a [`MemFile`] under a name,
+//! and other processes can [look it up][Client::lookup] by name to receive the file descriptor.
+//! The file descriptors are transferred using `SCM_RIGHTS` messages.
+//!
+//! The registry checks the seals of every published file.
+//! A publisher can specify which seals the file must have,
+//! and the registry itself can enforce a minimum set of seals for all files with [`Registry::required_seals()`].
+//! Files that do not have all required seals are rejected.
+//!
+//! # Example
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use memfile::{MemFile, Seal};
+//! use memfile::registry::{Client, Registry};
+//! use std::os::unix::net::SocketAddr;
+//!
+//! let addr = SocketAddr::from_pathname("/run/my-app/registry.sock")?;
+//!
+//! // In the registry process:
+//! let registry = Registry::bind(&addr)?.required_seals(Seal::Shrink | Seal::Grow);
+//! std::thread::spawn(move || registry.serve());
+//!
+//! // In the publishing process:
+//! let file = MemFile::create_sealable("frame")?;
+//! file.set_len(4096)?;
+//! file.add_seals(Seal::Shrink | Seal::Grow)?;
+//! Client::connect(&addr)?.publish("frame", &file, Seal::Shrink | Seal::Grow)?;
+//!
+//! // In the consuming process:
+//! let file = Client::connect(&addr)?.lookup("frame")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
+use std::os::unix::net::{SocketAddr, UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
+
+use crate::{sys, MemFile, Seals};
+
+/// Maximum length of a name in the registry, in bytes.
+pub const MAX_NAME_LEN: usize = 4096;
+
+/// Request to publish a file, with the file descriptor attached.
+const OP_PUBLISH: u8 = 1;
+
+/// Request to look up a file.
+const OP_LOOKUP: u8 = 2;
+
+/// Size of a request header: the operation, the required seals and the length of the name.
+const REQUEST_HEADER_LEN: usize = 9;
+
+/// Size of a response header: the status and the seals of the file.
+const RESPONSE_HEADER_LEN: usize = 5;
+
+/// Status codes sent in a response.
+mod status {
+	pub const OK: u8 = 0;
+	pub const NOT_FOUND: u8 = 1;
+	pub const ALREADY_EXISTS: u8 = 2;
+	pub const MISSING_SEALS: u8 = 3;
+	pub const NOT_A_MEMFD: u8 = 4;
+	pub const INVALID_REQUEST: u8 = 5;
+}
+
+/// A registry server that allows processes to publish and look up [`MemFile`]s by name.
+///
+/// See the [module documentation][self] for more information.
+#[derive(Debug)]
+pub struct Registry {
+	listener: UnixListener,
+	required_seals: Seals,
+	entries: Arc<Mutex<HashMap<String, OwnedFd>>>,
+}
+
+impl Registry {
+	/// Create a registry listening on the given socket address.
+	///
+	/// On Linux, you can use [`std::os::linux::net::SocketAddrExt::from_abstract_name()`] to use the abstract namespace,
+	/// which does not require a file system path.
+	pub fn bind(addr: &SocketAddr) -> std::io::Result<Self> {
+		Ok(Self::from_listener(UnixListener::bind_addr(addr)?))
+	}
+
+	/// Create a registry that accepts connections on an existing listener.
+	pub fn from_listener(listener: UnixListener) -> Self {
+		Self {
+			listener,
+			required_seals: Seals::empty(),
+			entries: Default::default(),
+		}
+	}
+
+	/// Set the seals that every published file must have, in addition to the seals requested by the publisher.
+	///
+	/// By default, no seals are required.
+	pub fn required_seals(mut self, seals: impl Into<Seals>) -> Self {
+		self.required_seals = seals.into();
+		self
+	}
+
+	/// Get the local address of the registry.
+	pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+		self.listener.local_addr()
+	}
+
+	/// Accept connections and serve them forever.
+	///
+	/// Each connection is served in a separate thread.
+	/// This function only returns if accepting a connection fails.
+	pub fn serve(&self) -> std::io::Result<()> {
+		loop {
+			self.accept()?;
+		}
+	}
+
+	/// Accept a single connection and serve it in a new thread.
+	pub fn accept(&self) -> std::io::Result<()> {
+		let (stream, _addr) = self.listener.accept()?;
+		let entries = self.entries.clone();
+		let required_seals = self.required_seals;
+		std::thread::spawn(move || {
+			// Errors only affect this connection, so ignore them.
+			let _ = serve_connection(stream, &entries, required_seals);
+		});
+		Ok(())
+	}
+}
+
+/// Serve requests on a single connection until it is closed.
+fn serve_connection(mut stream: UnixStream, entries: &Mutex<HashMap<String, OwnedFd>>, required_seals: Seals) -> std::io::Result<()> {
+	loop {
+		let mut header = [0u8; REQUEST_HEADER_LEN];
+		let (received, fd) = sys::recv_with_fd(stream.as_fd(), &mut header)?;
+		if received == 0 {
+			return Ok(());
+		}
+		stream.read_exact(&mut header[received..])?;
+
+		let op = header[0];
+		let requested_seals = Seals::from_bits_truncate(u32::from_le_bytes(header[1..5].try_into().unwrap()));
+		let name_len = u32::from_le_bytes(header[5..9].try_into().unwrap()) as usize;
+		if name_len > MAX_NAME_LEN {
+			send_response(&stream, status::INVALID_REQUEST, Seals::empty(), None)?;
+			return Ok(());
+		}
+		let mut name = vec![0; name_len];
+		stream.read_exact(&mut name)?;
+		let name = match String::from_utf8(name) {
+			Ok(x) => x,
+			Err(_) => {
+				send_response(&stream, status::INVALID_REQUEST, Seals::empty(), None)?;
+				continue;
+			},
+		};
+
+		match (op, fd) {
+			(OP_PUBLISH, Some(fd)) => {
+				let (status, seals) = publish(entries, name, fd, requested_seals | required_seals);
+				send_response(&stream, status, seals, None)?;
+			},
+			(OP_LOOKUP, None) => {
+				// Don't hold the lock while sending the response to a possibly slow client.
+				let fd = entries.lock().unwrap().get(&name).map(|fd| fd.try_clone()).transpose()?;
+				match fd {
+					Some(fd) => {
+						let seals = get_seals(&fd).unwrap_or(Seals::empty());
+						send_response(&stream, status::OK, seals, Some(fd.as_fd()))?;
+					},
+					None => send_response(&stream, status::NOT_FOUND, Seals::empty(), None)?,
+				}
+			},
+			_ => send_response(&stream, status::INVALID_REQUEST, Seals::empty(), None)?,
+		}
+	}
+}
+
+/// Check the seal policy for a file and add it to the registry.
+fn publish(entries: &Mutex<HashMap<String, OwnedFd>>, name: String, fd: OwnedFd, required_seals: Seals) -> (u8, Seals) {
+	// Check the seals on the received file descriptor, don't trust the publisher.
+	let seals = match get_seals(&fd) {
+		Ok(x) => x,
+		Err(_) => return (status::NOT_A_MEMFD, Seals::empty()),
+	};
+	if !seals.contains(required_seals) {
+		return (status::MISSING_SEALS, seals);
+	}
+
+	let mut entries = entries.lock().unwrap();
+	if entries.contains_key(&name) {
+		return (status::ALREADY_EXISTS, seals);
+	}
+	entries.insert(name, fd);
+	(status::OK, seals)
+}
+
+/// Get the seals of a file descriptor.
+fn get_seals(fd: &OwnedFd) -> std::io::Result<Seals> {
+	let seals = sys::memfd_get_seals(fd.as_fd())?;
+	Ok(Seals::from_bits_truncate(seals as u32))
+}
+
+/// Send a response to a client.
+fn send_response(stream: &UnixStream, status: u8, seals: Seals, fd: Option<BorrowedFd>) -> std::io::Result<()> {
+	let mut response = [0u8; RESPONSE_HEADER_LEN];
+	response[0] = status;
+	response[1..5].copy_from_slice(&seals.bits().to_le_bytes());
+	send_all(stream, &response, fd)
+}
+
+/// Send a message, with an optional file descriptor attached to the first byte.
+fn send_all(mut stream: &UnixStream, data: &[u8], fd: Option<BorrowedFd>) -> std::io::Result<()> {
+	let sent = sys::send_with_fd(stream.as_fd(), data, fd)?;
+	stream.write_all(&data[sent..])
+}
+
+/// A client for a [`Registry`].
+///
+/// See the [module documentation][self] for more information.
+#[derive(Debug)]
+pub struct Client {
+	stream: UnixStream,
+}
+
+impl Client {
+	/// Connect to a registry at the given socket address.
+	pub fn connect(addr: &SocketAddr) -> std::io::Result<Self> {
+		Ok(Self::from_stream(UnixStream::connect_addr(addr)?))
+	}
+
+	/// Create a client from an existing connection to a registry.
+	pub fn from_stream(stream: UnixStream) -> Self {
+		Self { stream }
+	}
+
+	/// Publish a file in the registry under the given name.
+	///
+	/// The registry verifies that the file has at least the `required_seals` and the seals required by the registry itself.
+	/// If not, this function fails with an error of kind [`std::io::ErrorKind::PermissionDenied`].
+	///
+	/// Names can not be published twice.
+	/// If the name is already in use, this function fails with an error of kind [`std::io::ErrorKind::AlreadyExists`].
+	pub fn publish(&mut self, name: &str, file: &MemFile, required_seals: impl Into<Seals>) -> std::io::Result<()> {
+		let request = encode_request(OP_PUBLISH, name, required_seals.into())?;
+		send_all(&self.stream, &request, Some(file.as_fd()))?;
+		let (_seals, _fd) = self.read_response()?;
+		Ok(())
+	}
+
+	/// Look up a file in the registry by name.
+	///
+	/// If no file was published under the name, this function fails with an error of kind [`std::io::ErrorKind::NotFound`].
+	///
+	/// The returned [`MemFile`] shares the file position with the published file and with all other lookups.
+	/// You should use positional I/O or memory mappings to access the contents.
+	pub fn lookup(&mut self, name: &str) -> std::io::Result<MemFile> {
+		let request = encode_request(OP_LOOKUP, name, Seals::empty())?;
+		send_all(&self.stream, &request, None)?;
+		let (_seals, fd) = self.read_response()?;
+		let fd = fd.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "registry did not send a file descriptor"))?;
+		Ok(MemFile::from_fd(fd)?)
+	}
+
+	/// Read a response from the registry.
+	fn read_response(&mut self) -> std::io::Result<(Seals, Option<OwnedFd>)> {
+		let mut response = [0u8; RESPONSE_HEADER_LEN];
+		let (received, fd) = sys::recv_with_fd(self.stream.as_fd(), &mut response)?;
+		if received == 0 {
+			return Err(std::io::ErrorKind::UnexpectedEof.into());
+		}
+		self.stream.read_exact(&mut response[received..])?;
+
+		let seals = Seals::from_bits_truncate(u32::from_le_bytes(response[1..5].try_into().unwrap()));
+		match response[0] {
+			status::OK => Ok((seals, fd)),
+			status::NOT_FOUND => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no file published under that name")),
+			status::ALREADY_EXISTS => Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, "a file is already published under that name")),
+			status::MISSING_SEALS => Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, format!("file does not have the required seals, it only has {seals:?}"))),
+			status::NOT_A_MEMFD => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "file was not created by memfd_create")),
+			_ => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "registry rejected the request")),
+		}
+	}
+}
+
+/// Encode a request for the registry.
+fn encode_request(op: u8, name: &str, seals: Seals) -> std::io::Result<Vec<u8>> {
+	if name.len() > MAX_NAME_LEN {
+		return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "name is too long for the registry"));
+	}
+	let mut request = Vec::with_capacity(REQUEST_HEADER_LEN + name.len());
+	request.push(op);
+	request.extend_from_slice(&seals.bits().to_le_bytes());
+	request.extend_from_slice(&(name.len() as u32).to_le_bytes());
+	request.extend_from_slice(name.as_bytes());
+	Ok(request)
+}
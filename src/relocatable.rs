@@ -0,0 +1,756 @@
+//! Relocatable data structures that live inside a [`MemFileArena`].
+//!
+//! Other processes map the arena at a different address, so regular pointers and collections can not be shared through it.
+//! The types in this module store offsets instead of pointers:
+//! * [`RelPtr`] points to a value relative to its own position, so it stays valid wherever the arena is mapped.
+//! * [`ShmSlice`] is a slice of values with a fixed length.
+//! * [`ShmVec`] is a vector that grows by allocating from the arena.
+//! * [`ShmHashMap`] is a hash map with a fixed capacity.
+//!
+//! The values are meant to be created in place:
+//! allocate an empty value with [`MemFileArena::alloc()`], and then fill it through the pointer returned by [`MemFileArena::resolve()`].
+//! Moving a value out of the arena leaves its offsets pointing to the wrong place.
+//!
+//! Every access is bounds-checked against the allocated part of the arena, which never extends beyond the mapping of the file.
+//! If an offset points outside of it, the access fails with an error of kind [`std::io::ErrorKind::InvalidData`].
+//! When attaching to an arena that was filled by another process,
+//! use [`MemFileArena::attach_validated()`] or [`MemFileArena::validate()`] to check all offsets that are reachable from the root value before using it.
+//!
+//! The collections do not synchronize access between processes.
+//! You must make sure that no process modifies a value while another process is using it.
+//!
+//! The arena does not track which pointers refer to the same memory.
+//! Therefore, pointing a [`RelPtr`] to a value and getting mutable access to the target of a pointer or the values in a collection is `unsafe`:
+//! you must make sure that there are no other references to the same memory while it is accessed mutably.
+//!
+//! Only types that implement the [`Relocatable`] trait can be stored in the arena.
+//!
+//! # Example
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use memfile::MemFileArena;
+//! use memfile::relocatable::ShmVec;
+//!
+//! let arena = MemFileArena::create("arena", 4096)?;
+//! let root = arena.alloc(ShmVec::<u32>::new())?;
+//! let vec = unsafe { arena.resolve(root).unwrap().as_mut() };
+//! vec.push(&arena, 1)?;
+//! vec.push(&arena, 2)?;
+//!
+//! // Send the file and the offset of the root to another process, which attaches to the arena.
+//! let (other, vec) = MemFileArena::attach_validated(arena.file().try_clone()?, root)?;
+//! let vec = unsafe { vec.as_ref() };
+//! assert_eq!(vec.as_slice(&other)?, [1, 2]);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::alloc::Layout;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use crate::{Error, MemFile, MemFileArena, ShmPtr};
+
+/// The maximum number of nested pointers that are followed during validation.
+const MAX_VALIDATION_DEPTH: u32 = 128;
+
+/// A type that can be stored in a [`MemFileArena`] and shared with other processes.
+///
+/// # Safety
+/// Implementors must guarantee that:
+/// * every bit pattern is a valid value of the type, since other processes can write anything to the arena,
+/// * the type does not implement [`Drop`] and does not contain references or absolute pointers,
+/// * the layout of the type is fixed, for example with `#[repr(C)]`, so that all processes agree on it.
+pub unsafe trait Relocatable: Sized {
+	/// Check that all offsets in the value point inside the allocated part of the arena.
+	///
+	/// The default implementation does nothing, which is correct for types that do not contain offsets.
+	/// Types that contain a [`RelPtr`] or a collection from this module must validate those fields.
+	fn validate(&self, validator: &mut Validator) -> std::io::Result<()> {
+		let _ = validator;
+		Ok(())
+	}
+}
+
+/// The state of a validation pass, passed to [`Relocatable::validate()`].
+///
+/// The validator limits how deeply pointers are nested and how many values are reached through pointers,
+/// so that data with cycles is reported as an error instead of being validated forever.
+/// Data without cycles never reaches more values than fit in the allocated part of the arena,
+/// unless many pointers refer to the same values.
+#[derive(Debug)]
+pub struct Validator<'a> {
+	arena: &'a MemFileArena,
+	depth: u32,
+	remaining: u64,
+}
+
+impl<'a> Validator<'a> {
+	/// Create a validator for the values in an arena.
+	fn new(arena: &'a MemFileArena) -> Self {
+		Self {
+			arena,
+			depth: 0,
+			remaining: arena.used().saturating_add(1),
+		}
+	}
+
+	/// Get the arena that is being validated.
+	pub fn arena(&self) -> &'a MemFileArena {
+		self.arena
+	}
+
+	/// Validate values that are reached through a pointer.
+	///
+	/// Pointer types must use this function instead of calling [`Relocatable::validate()`] on their targets directly,
+	/// so that the values count towards the limits of the validator.
+	///
+	/// Fails with an error of kind [`std::io::ErrorKind::InvalidData`] if the limits are exceeded.
+	pub fn follow<T: Relocatable>(&mut self, values: &[T]) -> std::io::Result<()> {
+		if self.depth >= MAX_VALIDATION_DEPTH {
+			return Err(invalid_data("pointers are nested too deeply, the data may contain a cycle"));
+		}
+		self.remaining = self.remaining.checked_sub(values.len() as u64)
+			.ok_or_else(|| invalid_data("too many values are reachable through pointers, the data may contain a cycle"))?;
+		self.depth += 1;
+		let result = values.iter().try_for_each(|value| value.validate(self));
+		self.depth -= 1;
+		result
+	}
+}
+
+macro_rules! impl_relocatable {
+	($($type:ty),*) => {
+		$(unsafe impl Relocatable for $type {})*
+	};
+}
+
+// `usize` and `isize` are left out on purpose: their size depends on the process, so the layout would not be fixed.
+impl_relocatable!((), u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+unsafe impl<T: Relocatable, const N: usize> Relocatable for [T; N] {
+	fn validate(&self, validator: &mut Validator) -> std::io::Result<()> {
+		self.iter().try_for_each(|value| value.validate(validator))
+	}
+}
+
+unsafe impl<T: Relocatable> Relocatable for ShmPtr<T> {
+	fn validate(&self, validator: &mut Validator) -> std::io::Result<()> {
+		let value = validator.arena().resolve(*self)
+			.ok_or_else(|| invalid_data("offset points outside of the allocated part of the arena"))?;
+		validator.follow(std::slice::from_ref(unsafe { value.as_ref() }))
+	}
+}
+
+impl MemFileArena {
+	/// Attach to an existing arena and validate the root value of the data in it.
+	///
+	/// This combines [`Self::attach()`] and [`Self::validate()`],
+	/// and it is the recommended way to access an arena that was filled by another process.
+	pub fn attach_validated<T: Relocatable>(file: MemFile, root: ShmPtr<T>) -> Result<(Self, NonNull<T>), Error> {
+		let arena = Self::attach(file)?;
+		let root = arena.validate(root)?;
+		Ok((arena, root))
+	}
+
+	/// Get a pointer to the value referenced by a [`ShmPtr`] after checking all offsets that are reachable from it.
+	///
+	/// Use this after attaching to an arena that was filled by another process, to detect corrupted data before using it.
+	/// See [`Validator`] for the limits that protect against data with cycles.
+	///
+	/// Fails with an error of kind [`std::io::ErrorKind::InvalidData`] if any offset points outside of the allocated part of the arena,
+	/// or if the limits of the validation are exceeded.
+	pub fn validate<T: Relocatable>(&self, ptr: ShmPtr<T>) -> std::io::Result<NonNull<T>> {
+		ptr.validate(&mut Validator::new(self))?;
+		// Other processes can change the allocation pointer after validation, so check the pointer again.
+		self.resolve(ptr)
+			.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "offset points outside of the allocated part of the arena"))
+	}
+}
+
+/// A pointer to a value in a [`MemFileArena`], stored as an offset from the location of the pointer itself.
+///
+/// Because the offset is relative, the pointer remains valid when the arena is mapped at a different address.
+/// A [`RelPtr`] must be stored in the arena to be useful: moving it somewhere else changes the location it points to.
+///
+/// An offset of zero represents a null pointer.
+#[repr(C)]
+pub struct RelPtr<T> {
+	offset: i64,
+	_marker: PhantomData<T>,
+}
+
+impl<T> RelPtr<T> {
+	/// Create a null pointer.
+	pub const fn null() -> Self {
+		Self {
+			offset: 0,
+			_marker: PhantomData,
+		}
+	}
+
+	/// Check if the pointer is null.
+	pub fn is_null(&self) -> bool {
+		self.offset == 0
+	}
+
+	/// Make the pointer point to `target`, or make it null if `target` is null.
+	///
+	/// # Safety
+	/// The target must not be accessed mutably through this pointer while any other reference to it exists,
+	/// and it must not be accessed mutably through any other pointer or collection while it is accessed through this pointer.
+	/// In particular, the target must not be part of the storage of a collection from this module,
+	/// unless you make sure that the collection is not modified while the target is accessed through this pointer.
+	pub unsafe fn set(&mut self, target: *const T) {
+		self.offset = if target.is_null() {
+			0
+		} else {
+			(target as isize).wrapping_sub(self as *const Self as isize) as i64
+		};
+	}
+
+	/// Get the absolute address that the pointer points to, without any checks.
+	fn target(&self) -> *const T {
+		if self.is_null() {
+			std::ptr::null()
+		} else {
+			(self as *const Self).cast::<u8>().wrapping_offset(self.offset as isize).cast()
+		}
+	}
+
+	/// Get the target of the pointer after checking that `count` values fit in the arena.
+	///
+	/// If `count` is zero, a dangling pointer is returned without checking the offset.
+	fn checked(&self, arena: &MemFileArena, count: u64) -> std::io::Result<NonNull<T>> {
+		if count == 0 {
+			Ok(NonNull::dangling())
+		} else if self.is_null() {
+			Err(invalid_data("null pointer to non-empty data"))
+		} else {
+			arena.check_bounds(self.target(), count)
+		}
+	}
+}
+
+impl<T: Relocatable> RelPtr<T> {
+	/// Get a reference to the value that the pointer points to.
+	///
+	/// Returns `None` if the pointer is null.
+	pub fn get<'a>(&'a self, arena: &'a MemFileArena) -> std::io::Result<Option<&'a T>> {
+		if self.is_null() {
+			return Ok(None);
+		}
+		Ok(Some(unsafe { self.checked(arena, 1)?.as_ref() }))
+	}
+
+	/// Get a mutable reference to the value that the pointer points to.
+	///
+	/// Returns `None` if the pointer is null.
+	///
+	/// # Safety
+	/// No other references to the target may exist while the returned reference is alive, in this or any other process.
+	/// Note that other pointers in the arena may refer to the same target.
+	pub unsafe fn get_mut<'a>(&'a mut self, arena: &'a MemFileArena) -> std::io::Result<Option<&'a mut T>> {
+		if self.is_null() {
+			return Ok(None);
+		}
+		Ok(Some(unsafe { self.checked(arena, 1)?.as_mut() }))
+	}
+}
+
+unsafe impl<T: Relocatable> Relocatable for RelPtr<T> {
+	fn validate(&self, validator: &mut Validator) -> std::io::Result<()> {
+		match self.get(validator.arena())? {
+			Some(value) => validator.follow(std::slice::from_ref(value)),
+			None => Ok(()),
+		}
+	}
+}
+
+impl<T> Default for RelPtr<T> {
+	fn default() -> Self {
+		Self::null()
+	}
+}
+
+impl<T> std::fmt::Debug for RelPtr<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_tuple("RelPtr")
+			.field(&self.offset)
+			.finish()
+	}
+}
+
+/// A slice of values in a [`MemFileArena`] with a length that is fixed when the storage is allocated.
+#[repr(C)]
+pub struct ShmSlice<T> {
+	data: RelPtr<T>,
+	len: u64,
+}
+
+impl<T> ShmSlice<T> {
+	/// Create an empty slice.
+	pub const fn new() -> Self {
+		Self {
+			data: RelPtr::null(),
+			len: 0,
+		}
+	}
+
+	/// Get the number of values in the slice.
+	pub fn len(&self) -> u64 {
+		self.len
+	}
+
+	/// Check if the slice is empty.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+}
+
+impl<T: Relocatable> ShmSlice<T> {
+	/// Allocate storage for `len` values in the arena and set all of them to zero.
+	///
+	/// The previous storage of the slice is released.
+	pub fn allocate_zeroed(&mut self, arena: &MemFileArena, len: u64) -> std::io::Result<()> {
+		let data = allocate_array::<T>(arena, len)?;
+		unsafe { data.as_ptr().write_bytes(0, len as usize) };
+		self.replace(arena, data, len);
+		Ok(())
+	}
+
+	/// Allocate storage in the arena and copy `values` into it.
+	///
+	/// The previous storage of the slice is released.
+	pub fn allocate_copy(&mut self, arena: &MemFileArena, values: &[T]) -> std::io::Result<()>
+	where
+		T: Copy,
+	{
+		let data = allocate_array::<T>(arena, values.len() as u64)?;
+		unsafe { data.as_ptr().copy_from_nonoverlapping(values.as_ptr(), values.len()) };
+		self.replace(arena, data, values.len() as u64);
+		Ok(())
+	}
+
+	/// Get the values as a regular slice.
+	pub fn as_slice<'a>(&'a self, arena: &'a MemFileArena) -> std::io::Result<&'a [T]> {
+		let data = self.data.checked(arena, self.len)?;
+		Ok(unsafe { std::slice::from_raw_parts(data.as_ptr(), self.len as usize) })
+	}
+
+	/// Get the values as a regular mutable slice.
+	///
+	/// # Safety
+	/// No other references to the values may exist while the returned slice is alive, in this or any other process.
+	/// Note that pointers elsewhere in the arena may refer to the same values.
+	pub unsafe fn as_mut_slice<'a>(&'a mut self, arena: &'a MemFileArena) -> std::io::Result<&'a mut [T]> {
+		let data = self.data.checked(arena, self.len)?;
+		Ok(unsafe { std::slice::from_raw_parts_mut(data.as_ptr(), self.len as usize) })
+	}
+
+	/// Release the current storage and point the slice to new storage.
+	fn replace(&mut self, arena: &MemFileArena, data: NonNull<T>, len: u64) {
+		release_array(arena, &self.data, self.len);
+		// The storage was just allocated, so nothing else refers to it.
+		unsafe { self.data.set(data.as_ptr()) };
+		self.len = len;
+	}
+}
+
+unsafe impl<T: Relocatable> Relocatable for ShmSlice<T> {
+	fn validate(&self, validator: &mut Validator) -> std::io::Result<()> {
+		validator.follow(self.as_slice(validator.arena())?)
+	}
+}
+
+impl<T> Default for ShmSlice<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T> std::fmt::Debug for ShmSlice<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("ShmSlice")
+			.field("data", &self.data)
+			.field("len", &self.len)
+			.finish()
+	}
+}
+
+/// A vector in a [`MemFileArena`] that grows by allocating new storage from the arena.
+///
+/// When the vector grows, the old storage is released, but the arena only reclaims it if it was the most recent allocation.
+/// Use [`Self::reserve()`] to allocate enough storage up front if you know how many values will be added.
+#[repr(C)]
+pub struct ShmVec<T> {
+	data: RelPtr<T>,
+	len: u64,
+	capacity: u64,
+}
+
+impl<T> ShmVec<T> {
+	/// Create an empty vector without storage.
+	pub const fn new() -> Self {
+		Self {
+			data: RelPtr::null(),
+			len: 0,
+			capacity: 0,
+		}
+	}
+
+	/// Get the number of values in the vector.
+	pub fn len(&self) -> u64 {
+		self.len
+	}
+
+	/// Check if the vector is empty.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Get the number of values the vector can hold without allocating new storage.
+	pub fn capacity(&self) -> u64 {
+		self.capacity
+	}
+
+	/// Remove all values from the vector without releasing the storage.
+	pub fn clear(&mut self) {
+		self.len = 0;
+	}
+}
+
+impl<T: Relocatable> ShmVec<T> {
+	/// Make sure the vector has room for at least `additional` more values.
+	pub fn reserve(&mut self, arena: &MemFileArena, additional: u64) -> std::io::Result<()> {
+		let required = self.len.checked_add(additional)
+			.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::OutOfMemory, "capacity overflow"))?;
+		if required <= self.capacity {
+			return Ok(());
+		}
+		let capacity = required.max(self.capacity.saturating_mul(2)).max(4);
+		let old = self.storage(arena)?;
+		let data = allocate_array::<T>(arena, capacity)?;
+		unsafe { data.as_ptr().copy_from_nonoverlapping(old.as_ptr(), self.len as usize) };
+		release_array(arena, &self.data, self.capacity);
+		// The storage was just allocated, so nothing else refers to it.
+		unsafe { self.data.set(data.as_ptr()) };
+		self.capacity = capacity;
+		Ok(())
+	}
+
+	/// Add a value to the end of the vector.
+	pub fn push(&mut self, arena: &MemFileArena, value: T) -> std::io::Result<()> {
+		self.reserve(arena, 1)?;
+		let data = self.storage(arena)?;
+		unsafe { data.as_ptr().add(self.len as usize).write(value) };
+		self.len += 1;
+		Ok(())
+	}
+
+	/// Remove the last value from the vector and return it.
+	pub fn pop(&mut self, arena: &MemFileArena) -> std::io::Result<Option<T>> {
+		if self.len == 0 {
+			return Ok(None);
+		}
+		let data = self.storage(arena)?;
+		self.len -= 1;
+		Ok(Some(unsafe { data.as_ptr().add(self.len as usize).read() }))
+	}
+
+	/// Get the values as a regular slice.
+	pub fn as_slice<'a>(&'a self, arena: &'a MemFileArena) -> std::io::Result<&'a [T]> {
+		let data = self.storage(arena)?;
+		Ok(unsafe { std::slice::from_raw_parts(data.as_ptr(), self.len as usize) })
+	}
+
+	/// Get the values as a regular mutable slice.
+	///
+	/// # Safety
+	/// No other references to the values may exist while the returned slice is alive, in this or any other process.
+	/// Note that pointers elsewhere in the arena may refer to the same values.
+	pub unsafe fn as_mut_slice<'a>(&'a mut self, arena: &'a MemFileArena) -> std::io::Result<&'a mut [T]> {
+		let data = self.storage(arena)?;
+		Ok(unsafe { std::slice::from_raw_parts_mut(data.as_ptr(), self.len as usize) })
+	}
+
+	/// Get a pointer to the storage of the vector after checking the capacity and length.
+	fn storage(&self, arena: &MemFileArena) -> std::io::Result<NonNull<T>> {
+		if self.len > self.capacity {
+			return Err(invalid_data("length of vector exceeds its capacity"));
+		}
+		self.data.checked(arena, self.capacity)
+	}
+}
+
+unsafe impl<T: Relocatable> Relocatable for ShmVec<T> {
+	fn validate(&self, validator: &mut Validator) -> std::io::Result<()> {
+		validator.follow(self.as_slice(validator.arena())?)
+	}
+}
+
+impl<T> Default for ShmVec<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T> std::fmt::Debug for ShmVec<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("ShmVec")
+			.field("data", &self.data)
+			.field("len", &self.len)
+			.field("capacity", &self.capacity)
+			.finish()
+	}
+}
+
+/// A hash map in a [`MemFileArena`] with a fixed number of buckets.
+///
+/// The storage for the buckets is allocated once with [`Self::allocate()`], and the map never grows after that.
+/// Collisions are resolved with linear probing, so the map can hold as many entries as it has buckets.
+///
+/// Keys are hashed with FNV-1a, so all processes find the same bucket for a key,
+/// as long as they use the same [`Hash`] implementation for the key type.
+#[repr(C)]
+pub struct ShmHashMap<K, V> {
+	buckets: ShmSlice<Bucket<K, V>>,
+	len: u64,
+}
+
+/// A bucket in a [`ShmHashMap`].
+///
+/// Buckets start out zeroed, and any state other than [`OCCUPIED`] or [`REMOVED`] means that the bucket is empty.
+#[repr(C)]
+struct Bucket<K, V> {
+	state: u8,
+	key: K,
+	value: V,
+}
+
+/// The state of a bucket that contains an entry.
+const OCCUPIED: u8 = 1;
+
+/// The state of a bucket from which an entry has been removed.
+///
+/// Lookups continue probing past removed entries.
+const REMOVED: u8 = 2;
+
+unsafe impl<K: Relocatable, V: Relocatable> Relocatable for Bucket<K, V> {
+	fn validate(&self, validator: &mut Validator) -> std::io::Result<()> {
+		if self.state == OCCUPIED {
+			self.key.validate(validator)?;
+			self.value.validate(validator)?;
+		}
+		Ok(())
+	}
+}
+
+impl<K, V> ShmHashMap<K, V> {
+	/// Create an empty map without any buckets.
+	pub const fn new() -> Self {
+		Self {
+			buckets: ShmSlice::new(),
+			len: 0,
+		}
+	}
+
+	/// Get the number of entries in the map.
+	pub fn len(&self) -> u64 {
+		self.len
+	}
+
+	/// Check if the map is empty.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Get the maximum number of entries the map can hold.
+	pub fn capacity(&self) -> u64 {
+		self.buckets.len()
+	}
+}
+
+impl<K: Relocatable + Hash + Eq, V: Relocatable> ShmHashMap<K, V> {
+	/// Allocate storage for `capacity` entries in the arena.
+	///
+	/// Any existing entries are discarded, and the previous storage of the map is released.
+	pub fn allocate(&mut self, arena: &MemFileArena, capacity: u64) -> std::io::Result<()> {
+		self.buckets.allocate_zeroed(arena, capacity)?;
+		self.len = 0;
+		Ok(())
+	}
+
+	/// Insert an entry in the map, and return the previous value for the key if there was one.
+	///
+	/// Fails with an error of kind [`std::io::ErrorKind::OutOfMemory`] if the key is new and all buckets are in use.
+	pub fn insert(&mut self, arena: &MemFileArena, key: K, value: V) -> std::io::Result<Option<V>> {
+		// Other pointers to the buckets can only be created with `RelPtr::set()`, which forbids modifying the map while they are used.
+		let buckets = unsafe { self.buckets.as_mut_slice(arena)? };
+		match find(buckets, &key) {
+			Probe::Found(index) => Ok(Some(std::mem::replace(&mut buckets[index].value, value))),
+			Probe::Vacant(index) => {
+				buckets[index] = Bucket { state: OCCUPIED, key, value };
+				self.len += 1;
+				Ok(None)
+			},
+			Probe::Full => Err(std::io::Error::new(std::io::ErrorKind::OutOfMemory, "hash map is full")),
+		}
+	}
+
+	/// Get a reference to the value for a key.
+	pub fn get<'a>(&'a self, arena: &'a MemFileArena, key: &K) -> std::io::Result<Option<&'a V>> {
+		let buckets = self.buckets.as_slice(arena)?;
+		match find(buckets, key) {
+			Probe::Found(index) => Ok(Some(&buckets[index].value)),
+			_ => Ok(None),
+		}
+	}
+
+	/// Get a mutable reference to the value for a key.
+	///
+	/// # Safety
+	/// No other references to the value may exist while the returned reference is alive, in this or any other process.
+	/// Note that pointers elsewhere in the arena may refer to the same value.
+	pub unsafe fn get_mut<'a>(&'a mut self, arena: &'a MemFileArena, key: &K) -> std::io::Result<Option<&'a mut V>> {
+		let buckets = self.buckets.as_mut_slice(arena)?;
+		match find(buckets, key) {
+			Probe::Found(index) => Ok(Some(&mut buckets[index].value)),
+			_ => Ok(None),
+		}
+	}
+
+	/// Check if the map contains a key.
+	pub fn contains_key(&self, arena: &MemFileArena, key: &K) -> std::io::Result<bool> {
+		Ok(self.get(arena, key)?.is_some())
+	}
+
+	/// Remove the entry for a key from the map, and return the value if there was one.
+	pub fn remove(&mut self, arena: &MemFileArena, key: &K) -> std::io::Result<Option<V>> {
+		// See `Self::insert()`.
+		let buckets = unsafe { self.buckets.as_mut_slice(arena)? };
+		match find(buckets, key) {
+			Probe::Found(index) => {
+				let bucket = &mut buckets[index];
+				bucket.state = REMOVED;
+				self.len = self.len.saturating_sub(1);
+				Ok(Some(unsafe { std::ptr::read(&bucket.value) }))
+			},
+			_ => Ok(None),
+		}
+	}
+
+	/// Iterate over all entries in the map, in no particular order.
+	pub fn iter<'a>(&'a self, arena: &'a MemFileArena) -> std::io::Result<impl Iterator<Item = (&'a K, &'a V)>> {
+		let buckets = self.buckets.as_slice(arena)?;
+		Ok(buckets.iter()
+			.filter(|bucket| bucket.state == OCCUPIED)
+			.map(|bucket| (&bucket.key, &bucket.value)))
+	}
+}
+
+unsafe impl<K: Relocatable, V: Relocatable> Relocatable for ShmHashMap<K, V> {
+	fn validate(&self, validator: &mut Validator) -> std::io::Result<()> {
+		self.buckets.validate(validator)
+	}
+}
+
+impl<K, V> Default for ShmHashMap<K, V> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<K, V> std::fmt::Debug for ShmHashMap<K, V> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("ShmHashMap")
+			.field("buckets", &self.buckets)
+			.field("len", &self.len)
+			.finish()
+	}
+}
+
+/// The result of looking up a key in the buckets of a hash map.
+enum Probe {
+	/// The key was found in the bucket with the given index.
+	Found(usize),
+
+	/// The key was not found, but it can be inserted in the bucket with the given index.
+	Vacant(usize),
+
+	/// The key was not found, and there is no room to insert it.
+	Full,
+}
+
+/// Look up a key in the buckets of a hash map using linear probing.
+fn find<K: Hash + Eq, V>(buckets: &[Bucket<K, V>], key: &K) -> Probe {
+	if buckets.is_empty() {
+		return Probe::Full;
+	}
+	let mut hasher = Fnv1a::new();
+	key.hash(&mut hasher);
+	let start = (hasher.finish() % buckets.len() as u64) as usize;
+
+	let mut vacant = None;
+	for i in 0..buckets.len() {
+		let index = (start + i) % buckets.len();
+		let bucket = &buckets[index];
+		match bucket.state {
+			OCCUPIED if bucket.key == *key => return Probe::Found(index),
+			OCCUPIED => (),
+			REMOVED => {
+				vacant.get_or_insert(index);
+			},
+			_ => return Probe::Vacant(vacant.unwrap_or(index)),
+		}
+	}
+	vacant.map_or(Probe::Full, Probe::Vacant)
+}
+
+/// The FNV-1a hash function, which gives the same results in every process.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+	fn new() -> Self {
+		Self(0xcbf2_9ce4_8422_2325)
+	}
+}
+
+impl Hasher for Fnv1a {
+	fn write(&mut self, bytes: &[u8]) {
+		for &byte in bytes {
+			self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+		}
+	}
+
+	fn finish(&self) -> u64 {
+		self.0
+	}
+}
+
+/// Allocate storage for `len` values of type `T` in the arena.
+fn allocate_array<T>(arena: &MemFileArena, len: u64) -> std::io::Result<NonNull<T>> {
+	let layout = usize::try_from(len).ok()
+		.and_then(|len| Layout::array::<T>(len).ok())
+		.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::OutOfMemory, "array is too large"))?;
+	Ok(arena.allocate_layout(layout)?.cast())
+}
+
+/// Release storage for `len` values that was allocated with [`allocate_array()`].
+///
+/// Nothing happens if the pointer is null or does not point to valid storage.
+fn release_array<T>(arena: &MemFileArena, data: &RelPtr<T>, len: u64) {
+	if data.is_null() || len == 0 {
+		return;
+	}
+	if let Ok(ptr) = data.checked(arena, len) {
+		let layout = Layout::array::<T>(len as usize).unwrap();
+		unsafe { arena.deallocate_layout(ptr.cast(), layout) };
+	}
+}
+
+/// Create an error of kind [`std::io::ErrorKind::InvalidData`].
+fn invalid_data(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
@@ -5,7 +5,7 @@ const SEAL_MASK: u32 = (libc::F_SEAL_SEAL | libc::F_SEAL_SHRINK | libc::F_SEAL_G
 const SEAL_MASK: u32 = (libc::F_SEAL_SEAL | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE) as u32;
 
 #[cfg(target_os = "linux")]
-const ALL_SEALS: [Seal; 5] = [
+pub(crate) const ALL_SEALS: [Seal; 5] = [
 	Seal::Seal,
 	Seal::Shrink,
 	Seal::Grow,
@@ -14,7 +14,7 @@ const ALL_SEALS: [Seal; 5] = [
 ];
 
 #[cfg(not(target_os = "linux"))]
-const ALL_SEALS: [Seal; 4] = [
+pub(crate) const ALL_SEALS: [Seal; 4] = [
 	Seal::Seal,
 	Seal::Shrink,
 	Seal::Grow,
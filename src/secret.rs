@@ -0,0 +1,120 @@
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::ptr::NonNull;
+
+use crate::sys;
+
+/// A secret memory area created by the `memfd_secret` syscall.
+///
+/// The memory of a [`SecretMemFile`] is removed from the kernel direct map,
+/// so it is not accessible to the kernel or to other processes, not even through `/proc/<pid>/mem` or `ptrace`.
+/// This makes it suitable for storing sensitive data like cryptographic keys.
+///
+/// The contents can only be accessed through a memory mapping, which is created together with the file.
+/// The kernel refuses regular I/O syscalls like `read` and `write` on secret memory,
+/// so this type does not implement [`std::io::Read`] or [`std::io::Write`].
+/// The file descriptor is also not exposed, to ensure the file can not be resized while it is mapped.
+///
+/// The memory is locked in RAM, so the size counts towards the `RLIMIT_MEMLOCK` resource limit of the process.
+/// The contents are overwritten with zeroes when the [`SecretMemFile`] is dropped.
+///
+/// The `memfd_secret` syscall is only available on Linux 5.14 and later.
+/// On some kernels it also needs to be enabled with the `secretmem.enable=1` kernel command line option.
+/// You can use [`Capabilities::memfd_secret()`][crate::Capabilities::memfd_secret] to check for support up front.
+pub struct SecretMemFile {
+	_file: File,
+	data: NonNull<u8>,
+	len: usize,
+}
+
+// The mapping is exclusively owned by the SecretMemFile, so it can be sent and shared like a `Vec<u8>`.
+unsafe impl Send for SecretMemFile {}
+unsafe impl Sync for SecretMemFile {}
+
+impl SecretMemFile {
+	/// Create a new [`SecretMemFile`] of the given size.
+	///
+	/// The memory is initially filled with zeroes.
+	///
+	/// If the kernel does not support `memfd_secret` or if it has been disabled,
+	/// this function returns an error of kind [`std::io::ErrorKind::Unsupported`].
+	///
+	/// The close-on-exec flag is set on the created file descriptor.
+	pub fn create(size: usize) -> std::io::Result<Self> {
+		let file = sys::memfd_secret(libc::O_CLOEXEC).map_err(|e| {
+			if e.raw_os_error() == Some(libc::ENOSYS) {
+				std::io::Error::new(std::io::ErrorKind::Unsupported, "memfd_secret is not supported or disabled by the kernel")
+			} else {
+				e
+			}
+		})?;
+
+		if size == 0 {
+			return Ok(Self {
+				_file: file,
+				data: NonNull::dangling(),
+				len: 0,
+			});
+		}
+
+		file.set_len(size as u64)?;
+		let data = sys::mmap(
+			file.as_raw_fd(),
+			size,
+			libc::PROT_READ | libc::PROT_WRITE,
+			libc::MAP_SHARED,
+			0,
+		)?;
+
+		Ok(Self {
+			_file: file,
+			data: NonNull::new(data.cast()).unwrap(),
+			len: size,
+		})
+	}
+
+	/// Get the size of the secret memory area in bytes.
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	/// Check if the secret memory area is empty.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Get the contents of the secret memory area as a slice.
+	pub fn as_slice(&self) -> &[u8] {
+		unsafe { std::slice::from_raw_parts(self.data.as_ptr(), self.len) }
+	}
+
+	/// Get the contents of the secret memory area as a mutable slice.
+	pub fn as_mut_slice(&mut self) -> &mut [u8] {
+		unsafe { std::slice::from_raw_parts_mut(self.data.as_ptr(), self.len) }
+	}
+}
+
+impl Drop for SecretMemFile {
+	fn drop(&mut self) {
+		if self.len == 0 {
+			return;
+		}
+		// Use volatile writes so the compiler can not optimize away the zeroing.
+		for byte in self.as_mut_slice() {
+			unsafe { std::ptr::write_volatile(byte, 0) };
+		}
+		std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+		unsafe {
+			let _ = sys::munmap(self.data.as_ptr().cast(), self.len);
+		}
+	}
+}
+
+impl std::fmt::Debug for SecretMemFile {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		// Never print the contents of the secret memory.
+		f.debug_struct("SecretMemFile")
+			.field("len", &self.len)
+			.finish_non_exhaustive()
+	}
+}
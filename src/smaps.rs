@@ -0,0 +1,79 @@
+use std::io::BufRead;
+
+/// A single memory mapping from `/proc/self/smaps`.
+#[derive(Debug)]
+pub(crate) struct SmapsEntry {
+	/// The start address of the mapping.
+	pub start: usize,
+
+	/// The major and minor device number of the mapped file.
+	pub dev: (u32, u32),
+
+	/// The inode number of the mapped file, or zero for anonymous mappings.
+	pub inode: u64,
+
+	/// The numeric fields of the entry, converted to bytes where needed.
+	fields: Vec<(String, u64)>,
+}
+
+impl SmapsEntry {
+	/// Get the value of a numeric field.
+	///
+	/// Sizes are reported in bytes, even though `/proc/self/smaps` uses kilobytes.
+	pub fn field(&self, name: &str) -> Option<u64> {
+		self.fields.iter()
+			.find(|(key, _)| key == name)
+			.map(|&(_, value)| value)
+	}
+}
+
+/// Read all entries from `/proc/self/smaps`.
+pub(crate) fn read() -> std::io::Result<Vec<SmapsEntry>> {
+	let file = std::fs::File::open("/proc/self/smaps")?;
+	let mut entries = Vec::new();
+	for line in std::io::BufReader::new(file).lines() {
+		let line = line?;
+		if let Some((start, dev, inode)) = parse_header(&line) {
+			entries.push(SmapsEntry { start, dev, inode, fields: Vec::new() });
+		} else if let (Some(entry), Some(field)) = (entries.last_mut(), parse_field(&line)) {
+			entry.fields.push(field);
+		}
+	}
+	Ok(entries)
+}
+
+/// Find the entry for the mapping that starts at the given address.
+pub(crate) fn find(start: *const u8) -> std::io::Result<SmapsEntry> {
+	read()?.into_iter()
+		.find(|entry| entry.start == start as usize)
+		.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "mapping not found in /proc/self/smaps"))
+}
+
+/// Parse the header line of an entry, like `7f0000000000-7f0000001000 rw-s 00000000 00:01 1234 /memfd:foo`.
+///
+/// Returns the start address, the device number and the inode number.
+fn parse_header(line: &str) -> Option<(usize, (u32, u32), u64)> {
+	let mut columns = line.split_ascii_whitespace();
+	let (start, end) = columns.next()?.split_once('-')?;
+	usize::from_str_radix(end, 16).ok()?;
+	let start = usize::from_str_radix(start, 16).ok()?;
+	let _permissions = columns.next()?;
+	let _offset = columns.next()?;
+	let (major, minor) = columns.next()?.split_once(':')?;
+	let dev = (u32::from_str_radix(major, 16).ok()?, u32::from_str_radix(minor, 16).ok()?);
+	let inode = columns.next()?.parse().ok()?;
+	Some((start, dev, inode))
+}
+
+/// Parse a numeric field like `Rss:    123 kB`.
+fn parse_field(line: &str) -> Option<(String, u64)> {
+	let (key, value) = line.split_once(':')?;
+	let mut value = value.split_ascii_whitespace();
+	let number: u64 = value.next()?.parse().ok()?;
+	let number = match value.next() {
+		Some("kB") => number.checked_mul(1024)?,
+		Some(_) => return None,
+		None => number,
+	};
+	Some((key.to_owned(), number))
+}
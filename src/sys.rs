@@ -1,32 +1,110 @@
 use std::fs::File;
 use std::os::raw::c_int;
 use std::ffi::CStr;
-use std::os::unix::io::FromRawFd;
-use std::os::unix::io::RawFd;
+use std::os::fd::{BorrowedFd, OwnedFd};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
 
-#[cfg(any(target_os = "linux", target_os = "freebsd"))]
-mod raw {
-	use std::os::raw::{c_char, c_int};
-	extern "C" {
-		pub fn memfd_create(name: *const c_char, flags: c_int) -> c_int;
+pub use memfd::{memfd_create_cstr, memfd_get_seals, memfd_add_seals};
+
+pub fn memfd_create(name: &str, flags: c_int) -> std::io::Result<File> {
+	let name = std::ffi::CString::new(name)?;
+	memfd_create_cstr(&name, flags)
+}
+
+#[cfg(not(feature = "rustix"))]
+mod memfd {
+	use std::ffi::CStr;
+	use std::fs::File;
+	use std::os::fd::BorrowedFd;
+	use std::os::raw::c_int;
+	use std::os::unix::io::{AsRawFd, FromRawFd};
+
+	#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+	mod raw {
+		use std::os::raw::{c_char, c_int};
+		extern "C" {
+			pub fn memfd_create(name: *const c_char, flags: c_int) -> c_int;
+		}
+	}
+
+	#[cfg(target_os = "android")]
+	mod raw {
+		use std::os::raw::{c_char, c_int};
+		pub unsafe fn memfd_create(name: *const c_char, flags: c_int) -> c_int {
+			libc::syscall(libc::SYS_memfd_create, name, flags) as c_int
+		}
+	}
+
+	pub fn memfd_create_cstr(name: &CStr, flags: c_int) -> std::io::Result<File> {
+		let fd = unsafe { raw::memfd_create(name.as_ptr(), flags) };
+		if fd < 0 {
+			Err(std::io::Error::last_os_error())
+		} else {
+			Ok(unsafe { File::from_raw_fd(fd) })
+		}
+	}
+
+	pub fn memfd_get_seals(fd: BorrowedFd) -> std::io::Result<c_int> {
+		match unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GET_SEALS) } {
+			-1 => Err(std::io::Error::last_os_error()),
+			seals => Ok(seals),
+		}
+	}
+
+	pub fn memfd_add_seals(fd: BorrowedFd, seals: c_int) -> std::io::Result<()> {
+		if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_ADD_SEALS, seals) } == 0 {
+			Ok(())
+		} else {
+			Err(std::io::Error::last_os_error())
+		}
 	}
 }
 
-#[cfg(target_os = "android")]
-mod raw {
-	use std::os::raw::{c_char, c_int};
-	pub unsafe fn memfd_create(name: *const c_char, flags: c_int) -> c_int {
-		libc::syscall(libc::SYS_memfd_create, name, flags) as c_int
+#[cfg(feature = "rustix")]
+mod memfd {
+	use std::ffi::CStr;
+	use std::fs::File;
+	use std::os::fd::BorrowedFd;
+	use std::os::raw::c_int;
+	use rustix::fs::{MemfdFlags, SealFlags};
+
+	pub fn memfd_create_cstr(name: &CStr, flags: c_int) -> std::io::Result<File> {
+		let fd = rustix::fs::memfd_create(name, MemfdFlags::from_bits_retain(flags as u32))?;
+		Ok(File::from(fd))
+	}
+
+	pub fn memfd_get_seals(fd: BorrowedFd) -> std::io::Result<c_int> {
+		let seals = rustix::fs::fcntl_get_seals(fd)?;
+		Ok(seals.bits() as c_int)
+	}
+
+	pub fn memfd_add_seals(fd: BorrowedFd, seals: c_int) -> std::io::Result<()> {
+		rustix::fs::fcntl_add_seals(fd, SealFlags::from_bits_retain(seals as u32))?;
+		Ok(())
 	}
 }
 
-pub fn memfd_create(name: &str, flags: c_int) -> std::io::Result<File> {
-	let name = std::ffi::CString::new(name)?;
-	memfd_create_cstr(&name, flags)
+/// Create a new shared memory object with `shm_open` and immediately unlink it.
+///
+/// Fails with `EEXIST` if a shared memory object with the given name already exists.
+///
+/// If unlinking fails, the file is still returned and the name remains in use until it is removed or the system reboots.
+/// Reporting an error instead would leave the object behind all the same, without giving the caller the file.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+pub fn shm_open_unlinked(name: &CStr) -> std::io::Result<File> {
+	let fd = unsafe { libc::shm_open(name.as_ptr(), libc::O_RDWR | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC, 0o600) };
+	if fd < 0 {
+		return Err(std::io::Error::last_os_error());
+	}
+	let file = unsafe { File::from_raw_fd(fd) };
+	unsafe { libc::shm_unlink(name.as_ptr()) };
+	Ok(file)
 }
 
-pub fn memfd_create_cstr(name: &CStr, flags: c_int) -> std::io::Result<File> {
-	let fd = unsafe { raw::memfd_create(name.as_ptr(), flags) };
+/// Create an unnamed temporary file in the given directory with `O_TMPFILE`.
+#[cfg(target_os = "linux")]
+pub fn open_tmpfile(dir: &CStr) -> std::io::Result<File> {
+	let fd = unsafe { libc::open(dir.as_ptr(), libc::O_TMPFILE | libc::O_RDWR | libc::O_CLOEXEC, 0o600) };
 	if fd < 0 {
 		Err(std::io::Error::last_os_error())
 	} else {
@@ -34,21 +112,345 @@ pub fn memfd_create_cstr(name: &CStr, flags: c_int) -> std::io::Result<File> {
 	}
 }
 
-pub fn memfd_get_seals(fd: RawFd) -> std::io::Result<c_int> {
-	match unsafe { libc::fcntl(fd, libc::F_GET_SEALS) } {
+#[cfg(target_os = "linux")]
+pub fn memfd_secret(flags: c_int) -> std::io::Result<File> {
+	let fd = unsafe { libc::syscall(libc::SYS_memfd_secret, flags) } as c_int;
+	if fd < 0 {
+		Err(std::io::Error::last_os_error())
+	} else {
+		Ok(unsafe { File::from_raw_fd(fd) })
+	}
+}
+
+/// Get the size of a memory page.
+pub fn page_size() -> usize {
+	match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+		size if size > 0 => size as usize,
+		_ => 4096,
+	}
+}
+
+/// Map a file in memory.
+pub fn mmap(fd: RawFd, len: usize, prot: c_int, flags: c_int, offset: u64) -> std::io::Result<*mut libc::c_void> {
+	let offset = to_off_t(offset)?;
+	let data = unsafe { libc::mmap(std::ptr::null_mut(), len, prot, flags, fd, offset) };
+	if data == libc::MAP_FAILED {
+		Err(std::io::Error::last_os_error())
+	} else {
+		Ok(data)
+	}
+}
+
+/// Unmap a memory mapping.
+///
+/// # Safety
+/// The memory region must not be used after it has been unmapped.
+pub unsafe fn munmap(data: *mut libc::c_void, len: usize) -> std::io::Result<()> {
+	if libc::munmap(data, len) == 0 {
+		Ok(())
+	} else {
+		Err(std::io::Error::last_os_error())
+	}
+}
+
+/// Resize a memory mapping, moving it to a different address if needed.
+///
+/// # Safety
+/// The old memory region must not be used after it has been remapped.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub unsafe fn mremap(data: *mut libc::c_void, old_len: usize, new_len: usize) -> std::io::Result<*mut libc::c_void> {
+	let data = libc::mremap(data, old_len, new_len, libc::MREMAP_MAYMOVE);
+	if data == libc::MAP_FAILED {
+		Err(std::io::Error::last_os_error())
+	} else {
+		Ok(data)
+	}
+}
+
+/// Copy memory from the address space of the current process using `process_vm_readv`.
+///
+/// Unlike a regular memory access, this fails with `EFAULT` instead of raising a signal if the memory can not be accessed.
+/// If only the start of the source can be accessed, the number of bytes that were copied is returned.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn read_own_memory(source: *const u8, buffer: &mut [u8]) -> std::io::Result<usize> {
+	let local = libc::iovec {
+		iov_base: buffer.as_mut_ptr().cast(),
+		iov_len: buffer.len(),
+	};
+	let remote = libc::iovec {
+		iov_base: source.cast_mut().cast(),
+		iov_len: buffer.len(),
+	};
+	let read = unsafe { libc::process_vm_readv(libc::getpid(), &local, 1, &remote, 1, 0) };
+	if read < 0 {
+		Err(std::io::Error::last_os_error())
+	} else {
+		Ok(read as usize)
+	}
+}
+
+/// Find the start of the next region with data at or after `offset`, using `lseek` with `SEEK_DATA`.
+///
+/// Returns `None` if there is no data at or after `offset`.
+/// This changes the file position.
+pub fn seek_data(fd: BorrowedFd, offset: u64) -> std::io::Result<Option<u64>> {
+	match unsafe { libc::lseek(fd.as_raw_fd(), to_off_t(offset)?, libc::SEEK_DATA) } {
+		-1 => match std::io::Error::last_os_error() {
+			e if e.raw_os_error() == Some(libc::ENXIO) => Ok(None),
+			e => Err(e),
+		},
+		position => Ok(Some(position as u64)),
+	}
+}
+
+/// Find the start of the next hole at or after `offset`, using `lseek` with `SEEK_HOLE`.
+///
+/// The end of the file counts as a hole.
+/// This changes the file position.
+pub fn seek_hole(fd: BorrowedFd, offset: u64) -> std::io::Result<u64> {
+	match unsafe { libc::lseek(fd.as_raw_fd(), to_off_t(offset)?, libc::SEEK_HOLE) } {
 		-1 => Err(std::io::Error::last_os_error()),
-		seals => Ok(seals),
+		position => Ok(position as u64),
 	}
 }
 
-pub fn memfd_add_seals(fd: RawFd, seals: c_int) -> std::io::Result<()> {
-	if unsafe { libc::fcntl(fd, libc::F_ADD_SEALS, seals) } == 0 {
+/// Give advice about the use of a memory region.
+pub fn madvise(data: *mut libc::c_void, len: usize, advice: c_int) -> std::io::Result<()> {
+	if unsafe { libc::madvise(data, len, advice) } == 0 {
 		Ok(())
 	} else {
 		Err(std::io::Error::last_os_error())
 	}
 }
 
+/// Lock a memory region in RAM.
+pub fn mlock(data: *const libc::c_void, len: usize) -> std::io::Result<()> {
+	if unsafe { libc::mlock(data, len) } == 0 {
+		Ok(())
+	} else {
+		Err(std::io::Error::last_os_error())
+	}
+}
+
+/// Unlock a memory region that was locked with `mlock`.
+pub fn munlock(data: *const libc::c_void, len: usize) -> std::io::Result<()> {
+	if unsafe { libc::munlock(data, len) } == 0 {
+		Ok(())
+	} else {
+		Err(std::io::Error::last_os_error())
+	}
+}
+
+/// Set the NUMA memory policy for a memory region.
+///
+/// The `nodemask` is a bitmask with one bit for each NUMA node.
+#[cfg(target_os = "linux")]
+pub fn mbind(data: *mut libc::c_void, len: usize, mode: c_int, nodemask: &[libc::c_ulong], flags: c_int) -> std::io::Result<()> {
+	let (mask, max_node) = if nodemask.is_empty() {
+		(std::ptr::null(), 0)
+	} else {
+		// The kernel ignores the last bit, so add one to the number of bits.
+		(nodemask.as_ptr(), nodemask.len() * libc::c_ulong::BITS as usize + 1)
+	};
+	let result = unsafe { libc::syscall(libc::SYS_mbind, data, len, mode, mask, max_node, flags as libc::c_uint) };
+	if result == 0 {
+		Ok(())
+	} else {
+		Err(std::io::Error::last_os_error())
+	}
+}
+
+/// Copy data between file descriptors using `copy_file_range`.
+///
+/// If an offset is given, it is used and updated instead of the file position of the file descriptor.
+#[cfg(target_os = "linux")]
+pub fn copy_file_range(fd_in: RawFd, off_in: Option<&mut libc::loff_t>, fd_out: RawFd, off_out: Option<&mut libc::loff_t>, len: usize) -> std::io::Result<usize> {
+	let off_in = off_in.map_or(std::ptr::null_mut(), |x| x as *mut _);
+	let off_out = off_out.map_or(std::ptr::null_mut(), |x| x as *mut _);
+	let copied = unsafe { libc::syscall(libc::SYS_copy_file_range, fd_in, off_in, fd_out, off_out, len, 0) };
+	if copied < 0 {
+		Err(std::io::Error::last_os_error())
+	} else {
+		Ok(copied as usize)
+	}
+}
+
+/// Copy data between file descriptors using `splice`.
+///
+/// One of the file descriptors must refer to a pipe.
+/// If an offset is given, it is used and updated instead of the file position of the file descriptor.
+#[cfg(target_os = "linux")]
+pub fn splice(fd_in: RawFd, off_in: Option<&mut libc::loff_t>, fd_out: RawFd, off_out: Option<&mut libc::loff_t>, len: usize) -> std::io::Result<usize> {
+	let off_in = off_in.map_or(std::ptr::null_mut(), |x| x as *mut _);
+	let off_out = off_out.map_or(std::ptr::null_mut(), |x| x as *mut _);
+	let copied = unsafe { libc::splice(fd_in, off_in, fd_out, off_out, len, libc::SPLICE_F_MOVE) };
+	if copied < 0 {
+		Err(std::io::Error::last_os_error())
+	} else {
+		Ok(copied as usize)
+	}
+}
+
+/// Copy data from a file to another file descriptor using `sendfile`.
+///
+/// The offset is used and updated instead of the file position of the input file descriptor.
+#[cfg(target_os = "linux")]
+pub fn sendfile(fd_out: RawFd, fd_in: RawFd, offset: &mut libc::off_t, len: usize) -> std::io::Result<usize> {
+	let copied = unsafe { libc::sendfile(fd_out, fd_in, offset, len) };
+	if copied < 0 {
+		Err(std::io::Error::last_os_error())
+	} else {
+		Ok(copied as usize)
+	}
+}
+
+/// Read into multiple buffers at the given offset using `preadv`.
+pub fn preadv(fd: RawFd, bufs: &mut [std::io::IoSliceMut<'_>], offset: u64) -> std::io::Result<usize> {
+	let offset = to_off_t(offset)?;
+	let count = bufs.len().min(c_int::MAX as usize) as c_int;
+	// IoSliceMut is guaranteed to be ABI compatible with `iovec` on Unix.
+	let read = unsafe { libc::preadv(fd, bufs.as_mut_ptr().cast(), count, offset) };
+	if read < 0 {
+		Err(std::io::Error::last_os_error())
+	} else {
+		Ok(read as usize)
+	}
+}
+
+/// Write from multiple buffers at the given offset using `pwritev`.
+pub fn pwritev(fd: RawFd, bufs: &[std::io::IoSlice<'_>], offset: u64) -> std::io::Result<usize> {
+	let offset = to_off_t(offset)?;
+	let count = bufs.len().min(c_int::MAX as usize) as c_int;
+	// IoSlice is guaranteed to be ABI compatible with `iovec` on Unix.
+	let written = unsafe { libc::pwritev(fd, bufs.as_ptr().cast(), count, offset) };
+	if written < 0 {
+		Err(std::io::Error::last_os_error())
+	} else {
+		Ok(written as usize)
+	}
+}
+
+/// Read into multiple buffers at the given offset using `preadv2`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn preadv2(fd: RawFd, bufs: &mut [std::io::IoSliceMut<'_>], offset: u64, flags: c_int) -> std::io::Result<usize> {
+	let offset = to_off_t(offset)?;
+	let count = bufs.len().min(c_int::MAX as usize) as c_int;
+	// IoSliceMut is guaranteed to be ABI compatible with `iovec` on Unix.
+	let read = unsafe { libc::preadv2(fd, bufs.as_mut_ptr().cast(), count, offset, flags) };
+	if read < 0 {
+		Err(std::io::Error::last_os_error())
+	} else {
+		Ok(read as usize)
+	}
+}
+
+/// Write from multiple buffers at the given offset using `pwritev2`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn pwritev2(fd: RawFd, bufs: &[std::io::IoSlice<'_>], offset: u64, flags: c_int) -> std::io::Result<usize> {
+	let offset = to_off_t(offset)?;
+	let count = bufs.len().min(c_int::MAX as usize) as c_int;
+	// IoSlice is guaranteed to be ABI compatible with `iovec` on Unix.
+	let written = unsafe { libc::pwritev2(fd, bufs.as_ptr().cast(), count, offset, flags) };
+	if written < 0 {
+		Err(std::io::Error::last_os_error())
+	} else {
+		Ok(written as usize)
+	}
+}
+
+/// Send data over a Unix socket, optionally with a file descriptor attached using `SCM_RIGHTS`.
+pub fn send_with_fd(socket: BorrowedFd, data: &[u8], fd: Option<BorrowedFd>) -> std::io::Result<usize> {
+	let mut iov = libc::iovec {
+		iov_base: data.as_ptr() as *mut libc::c_void,
+		iov_len: data.len(),
+	};
+	let mut control = [0u64; CONTROL_LEN];
+	let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+	header.msg_iov = &mut iov;
+	header.msg_iovlen = 1;
+
+	if let Some(fd) = fd {
+		header.msg_control = control.as_mut_ptr().cast();
+		header.msg_controllen = unsafe { libc::CMSG_SPACE(std::mem::size_of::<c_int>() as u32) } as _;
+		unsafe {
+			let cmsg = libc::CMSG_FIRSTHDR(&header);
+			(*cmsg).cmsg_level = libc::SOL_SOCKET;
+			(*cmsg).cmsg_type = libc::SCM_RIGHTS;
+			(*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<c_int>() as u32) as _;
+			std::ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast::<c_int>(), fd.as_raw_fd());
+		}
+	}
+
+	loop {
+		let sent = unsafe { libc::sendmsg(socket.as_raw_fd(), &header, libc::MSG_NOSIGNAL) };
+		if sent >= 0 {
+			return Ok(sent as usize);
+		}
+		let error = std::io::Error::last_os_error();
+		if error.kind() != std::io::ErrorKind::Interrupted {
+			return Err(error);
+		}
+	}
+}
+
+/// Receive data from a Unix socket, along with a file descriptor if one was attached using `SCM_RIGHTS`.
+///
+/// The close-on-exec flag is set on the received file descriptor.
+/// If more than one file descriptor was attached, the additional file descriptors are closed.
+pub fn recv_with_fd(socket: BorrowedFd, data: &mut [u8]) -> std::io::Result<(usize, Option<OwnedFd>)> {
+	let mut iov = libc::iovec {
+		iov_base: data.as_mut_ptr().cast(),
+		iov_len: data.len(),
+	};
+	let mut control = [0u64; CONTROL_LEN];
+	let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+	header.msg_iov = &mut iov;
+	header.msg_iovlen = 1;
+	header.msg_control = control.as_mut_ptr().cast();
+	header.msg_controllen = std::mem::size_of_val(&control) as _;
+
+	let received = loop {
+		let received = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut header, libc::MSG_CMSG_CLOEXEC) };
+		if received >= 0 {
+			break received as usize;
+		}
+		let error = std::io::Error::last_os_error();
+		if error.kind() != std::io::ErrorKind::Interrupted {
+			return Err(error);
+		}
+	};
+
+	// Take ownership of all received file descriptors so that extra ones are closed.
+	let mut fds = Vec::new();
+	unsafe {
+		let mut cmsg = libc::CMSG_FIRSTHDR(&header);
+		while !cmsg.is_null() {
+			if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+				let data = libc::CMSG_DATA(cmsg).cast::<c_int>();
+				let len = (*cmsg).cmsg_len as usize - (data as usize - cmsg as usize);
+				for i in 0..len / std::mem::size_of::<c_int>() {
+					fds.push(OwnedFd::from_raw_fd(std::ptr::read_unaligned(data.add(i))));
+				}
+			}
+			cmsg = libc::CMSG_NXTHDR(&header, cmsg);
+		}
+	}
+
+	if header.msg_flags & libc::MSG_CTRUNC != 0 {
+		return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "received too many file descriptors"));
+	}
+	Ok((received, fds.into_iter().next()))
+}
+
+/// Size of the control message buffer for sending or receiving a single file descriptor, in units of `u64`.
+const CONTROL_LEN: usize = 4;
+
+/// Convert a file offset to `off_t`.
+pub fn to_off_t(offset: u64) -> std::io::Result<libc::off_t> {
+	libc::off_t::try_from(offset)
+		.map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidInput))
+}
+
 #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
 pub mod flags {
 	// Linux values taken from:
@@ -63,6 +465,34 @@ pub mod flags {
 	pub const MFD_ALLOW_SEALING: c_int = 0x02;
 	pub const MFD_HUGETLB: c_int = 0x04;
 
+	// Only available on Linux 6.3 and later:
+	// https://github.com/torvalds/linux/blob/v6.3/include/uapi/linux/memfd.h
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub const MFD_NOEXEC_SEAL: c_int = 0x08;
+
+	// Not available in older versions of `libc`:
+	// https://github.com/torvalds/linux/blob/v6.1/include/uapi/asm-generic/mman-common.h
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub const MADV_POPULATE_READ: c_int = 22;
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub const MADV_POPULATE_WRITE: c_int = 23;
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub const MADV_COLLAPSE: c_int = 25;
+
+	// https://github.com/torvalds/linux/blob/v6.1/include/uapi/linux/mempolicy.h
+	#[cfg(target_os = "linux")]
+	pub const MPOL_DEFAULT: c_int = 0;
+	#[cfg(target_os = "linux")]
+	pub const MPOL_PREFERRED: c_int = 1;
+	#[cfg(target_os = "linux")]
+	pub const MPOL_BIND: c_int = 2;
+	#[cfg(target_os = "linux")]
+	pub const MPOL_INTERLEAVE: c_int = 3;
+	#[cfg(target_os = "linux")]
+	pub const MPOL_LOCAL: c_int = 4;
+	#[cfg(target_os = "linux")]
+	pub const MPOL_MF_MOVE: c_int = 1 << 1;
+
 	const MFD_HUGE_SHIFT: c_int = 26;
 	pub const MFD_HUGE_64KB: c_int = 16 << MFD_HUGE_SHIFT;
 	pub const MFD_HUGE_512KB: c_int = 19 << MFD_HUGE_SHIFT;
@@ -0,0 +1,168 @@
+use crate::mapping::RawMapping;
+use crate::{Error, GrowableMapping, MemFile};
+
+/// The path of the system setting for transparent huge pages in shared memory.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const SHMEM_ENABLED: &str = "/sys/kernel/mm/transparent_hugepage/shmem_enabled";
+
+/// How a [`MemFile`] should use transparent huge pages.
+///
+/// Unlike [`HugeTlb`][crate::HugeTlb], transparent huge pages do not require a pool of preallocated pages.
+/// Instead, the kernel tries to back shared memory with huge pages when it is mapped, and falls back to regular pages when needed.
+///
+/// The mode is applied with `madvise` to every mapping of the file created by this crate, such as a [`GrowableMapping`].
+/// It has no effect on mappings that you create yourself.
+///
+/// Transparent huge pages are only supported on Linux and Android.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ThpMode {
+	/// Ask the kernel to use transparent huge pages for mappings of the file with `MADV_HUGEPAGE`.
+	///
+	/// Whether the kernel actually uses huge pages depends on the [system setting][ShmemThpSetting].
+	Enable,
+
+	/// Like [`Self::Enable`], but fail to create the file if the system setting never allows huge pages for shared memory.
+	Require,
+
+	/// Ask the kernel not to use transparent huge pages for mappings of the file with `MADV_NOHUGEPAGE`.
+	Disable,
+}
+
+/// The system setting for transparent huge pages in shared memory.
+///
+/// This is read from `/sys/kernel/mm/transparent_hugepage/shmem_enabled`.
+/// See the kernel documentation for the meaning of each value:
+/// <https://www.kernel.org/doc/html/latest/admin-guide/mm/transhuge.html#hugepages-in-tmpfs-shmem>
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ShmemThpSetting {
+	/// Always attempt to allocate huge pages.
+	Always,
+
+	/// Only allocate huge pages if they fit completely within the file size.
+	WithinSize,
+
+	/// Only allocate huge pages for mappings with `MADV_HUGEPAGE`.
+	Advise,
+
+	/// Never allocate huge pages.
+	Never,
+
+	/// Disable huge pages for all shared memory, overriding `MADV_HUGEPAGE`.
+	Deny,
+
+	/// Force huge pages for all shared memory, used for testing.
+	Force,
+}
+
+impl ShmemThpSetting {
+	/// Read the current system setting.
+	///
+	/// Fails with an error of kind [`std::io::ErrorKind::Unsupported`] if the kernel does not support transparent huge pages.
+	pub fn current() -> std::io::Result<Self> {
+		#[cfg(any(target_os = "linux", target_os = "android"))]
+		{
+			let data = match std::fs::read_to_string(SHMEM_ENABLED) {
+				Ok(x) => x,
+				Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(unsupported()),
+				Err(e) => return Err(e),
+			};
+			Self::parse(&data)
+				.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid value in {SHMEM_ENABLED}: {data:?}")))
+		}
+
+		#[cfg(not(any(target_os = "linux", target_os = "android")))]
+		Err(unsupported())
+	}
+
+	/// Check if huge pages can be used for mappings with `MADV_HUGEPAGE`.
+	pub fn allows_huge_pages(self) -> bool {
+		!matches!(self, Self::Never | Self::Deny)
+	}
+
+	/// Parse the contents of the system setting, where the active value is surrounded by square brackets.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	fn parse(data: &str) -> Option<Self> {
+		let active = data.split_ascii_whitespace()
+			.find_map(|word| word.strip_prefix('[')?.strip_suffix(']'))?;
+		match active {
+			"always" => Some(Self::Always),
+			"within_size" => Some(Self::WithinSize),
+			"advise" => Some(Self::Advise),
+			"never" => Some(Self::Never),
+			"deny" => Some(Self::Deny),
+			"force" => Some(Self::Force),
+			_ => None,
+		}
+	}
+}
+
+/// Create the error for unsupported transparent huge pages.
+fn unsupported() -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::Unsupported, "transparent huge pages are not supported")
+}
+
+/// Check if a mode can be used before creating a file.
+pub(crate) fn check(mode: ThpMode) -> Result<(), Error> {
+	match mode {
+		ThpMode::Require => match ShmemThpSetting::current() {
+			Ok(setting) if setting.allows_huge_pages() => Ok(()),
+			Ok(_) => Err(Error::TransparentHugePagesUnavailable),
+			Err(e) if e.kind() == std::io::ErrorKind::Unsupported => Err(Error::TransparentHugePagesUnavailable),
+			Err(e) => Err(e.into()),
+		},
+		ThpMode::Enable | ThpMode::Disable => {
+			if cfg!(any(target_os = "linux", target_os = "android")) {
+				Ok(())
+			} else {
+				Err(Error::TransparentHugePagesUnavailable)
+			}
+		},
+	}
+}
+
+impl MemFile {
+	/// Apply the transparent huge page mode of the file to a new mapping.
+	pub(crate) fn apply_thp_mode(&self, mapping: &RawMapping) -> std::io::Result<()> {
+		#[cfg(any(target_os = "linux", target_os = "android"))]
+		if mapping.len() != 0 {
+			let advice = match self.thp_mode {
+				None => return Ok(()),
+				Some(ThpMode::Enable | ThpMode::Require) => libc::MADV_HUGEPAGE,
+				Some(ThpMode::Disable) => libc::MADV_NOHUGEPAGE,
+			};
+			crate::sys::madvise(mapping.as_ptr().cast(), mapping.len(), advice)?;
+		}
+		#[cfg(not(any(target_os = "linux", target_os = "android")))]
+		let _ = mapping;
+		Ok(())
+	}
+}
+
+impl RawMapping {
+	/// Get the number of bytes of the mapping that are backed by transparent huge pages.
+	pub fn huge_page_bytes(&self) -> std::io::Result<u64> {
+		#[cfg(any(target_os = "linux", target_os = "android"))]
+		{
+			if self.len() == 0 {
+				return Ok(0);
+			}
+			let entry = crate::smaps::find(self.as_ptr())?;
+			Ok(entry.field("ShmemPmdMapped").unwrap_or(0))
+		}
+
+		#[cfg(not(any(target_os = "linux", target_os = "android")))]
+		Err(unsupported())
+	}
+}
+
+impl GrowableMapping {
+	/// Get the number of bytes of the mapping that are currently backed by transparent huge pages.
+	///
+	/// This is read from the `ShmemPmdMapped` field in `/proc/self/smaps`.
+	/// Use [`CreateOptions::transparent_huge_pages()`][crate::CreateOptions::transparent_huge_pages] to request huge pages for a file.
+	pub fn huge_page_bytes(&self) -> std::io::Result<u64> {
+		self.raw().huge_page_bytes()
+	}
+}
@@ -0,0 +1,143 @@
+use std::os::unix::fs::MetadataExt;
+
+use crate::{sys, MemFile};
+
+/// Information about the memory used by a [`MemFile`], returned by [`MemFile::usage()`].
+///
+/// The logical size of a file says little about the memory it consumes:
+/// pages that were never written do not use any memory, and pages that were swapped out do not use any RAM.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MemoryUsage {
+	len: u64,
+	allocated_bytes: u64,
+	data_bytes: Option<u64>,
+	resident_pages: Option<u64>,
+	swapped_pages: Option<u64>,
+}
+
+impl MemoryUsage {
+	/// The logical size of the file in bytes.
+	pub fn len(&self) -> u64 {
+		self.len
+	}
+
+	/// Check if the logical size of the file is zero.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// The number of bytes allocated for the file, based on the `st_blocks` field of the file metadata.
+	///
+	/// This includes pages that have been swapped out.
+	pub fn allocated_bytes(&self) -> u64 {
+		self.allocated_bytes
+	}
+
+	/// The total size of all data extents in the file in bytes, as reported by `lseek` with `SEEK_DATA` and `SEEK_HOLE`.
+	///
+	/// The extents are rounded to whole pages by the kernel, so this may be slightly larger than the amount of data that was written.
+	///
+	/// This is `None` on platforms without `/proc/self/fd` or if the file could not be reopened through it.
+	pub fn data_bytes(&self) -> Option<u64> {
+		self.data_bytes
+	}
+
+	/// The number of pages of the file that are mapped in the current process and resident in RAM.
+	///
+	/// This is the sum of the `Rss` field in `/proc/self/smaps` for all mappings of the file, divided by the page size.
+	/// Pages that are mapped more than once are counted once for every mapping.
+	/// Pages that are only mapped by other processes or that are not mapped at all are not included.
+	///
+	/// This is `None` on platforms without `/proc/self/smaps` or if it could not be read.
+	pub fn resident_pages(&self) -> Option<u64> {
+		self.resident_pages
+	}
+
+	/// The number of pages of the file that are mapped in the current process and swapped out.
+	///
+	/// This is the sum of the `Swap` field in `/proc/self/smaps` for all mappings of the file, divided by the page size.
+	/// Like [`Self::resident_pages()`], pages that are mapped more than once are counted once for every mapping.
+	///
+	/// This is `None` on platforms without `/proc/self/smaps` or if it could not be read.
+	pub fn swapped_pages(&self) -> Option<u64> {
+		self.swapped_pages
+	}
+}
+
+impl MemFile {
+	/// Get information about the memory used by the file.
+	///
+	/// This looks at the file metadata, the data extents of the file and the mappings of the file in `/proc/self/smaps`.
+	/// See [`MemoryUsage`] for details about the reported values.
+	///
+	/// The data extents are found by seeking in a new open file description for the file, obtained through `/proc/self/fd`.
+	/// This does not affect the file position of this [`MemFile`] or of any other file descriptor for the same open file description,
+	/// such as clones created with [`Self::try_clone()`].
+	pub fn usage(&self) -> std::io::Result<MemoryUsage> {
+		let metadata = self.metadata()?;
+		let data_bytes = self.data_bytes(metadata.len())?;
+		let (resident_pages, swapped_pages) = match mapped_pages(&metadata) {
+			Some((resident, swapped)) => (Some(resident), Some(swapped)),
+			None => (None, None),
+		};
+		Ok(MemoryUsage {
+			len: metadata.len(),
+			allocated_bytes: metadata.blocks() * 512,
+			data_bytes,
+			resident_pages,
+			swapped_pages,
+		})
+	}
+
+	/// Get the total size of all data extents in the file.
+	///
+	/// Returns `None` if the file could not be reopened.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	fn data_bytes(&self, len: u64) -> std::io::Result<Option<u64>> {
+		use std::os::fd::{AsFd, AsRawFd};
+
+		// Reopening the file creates a new open file description, with a file position that is not shared with anyone.
+		let Ok(file) = std::fs::File::open(format!("/proc/self/fd/{}", self.as_raw_fd())) else { return Ok(None) };
+		let mut total = 0;
+		let mut offset = 0;
+		while offset < len {
+			let Some(start) = sys::seek_data(file.as_fd(), offset)? else { break };
+			let end = sys::seek_hole(file.as_fd(), start)?;
+			total += end - start;
+			offset = end;
+		}
+		Ok(Some(total))
+	}
+
+	/// Get the total size of all data extents in the file.
+	///
+	/// This is not supported on this platform.
+	#[cfg(not(any(target_os = "linux", target_os = "android")))]
+	fn data_bytes(&self, _len: u64) -> std::io::Result<Option<u64>> {
+		Ok(None)
+	}
+}
+
+/// Count the resident and swapped pages in all mappings of a file in `/proc/self/smaps`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn mapped_pages(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+	let dev = (libc::major(metadata.dev()) as u32, libc::minor(metadata.dev()) as u32);
+	let page_size = sys::page_size() as u64;
+	let mut resident = 0;
+	let mut swapped = 0;
+	for entry in crate::smaps::read().ok()? {
+		if entry.dev == dev && entry.inode == metadata.ino() {
+			resident += entry.field("Rss").unwrap_or(0) / page_size;
+			swapped += entry.field("Swap").unwrap_or(0) / page_size;
+		}
+	}
+	Some((resident, swapped))
+}
+
+/// Count the resident and swapped pages in all mappings of a file.
+///
+/// This is not supported on this platform.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn mapped_pages(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+	None
+}
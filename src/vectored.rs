@@ -0,0 +1,162 @@
+use std::borrow::Cow;
+use std::io::{IoSlice, IoSliceMut};
+use std::os::unix::io::AsRawFd;
+
+use crate::sys;
+use crate::MemFile;
+
+impl MemFile {
+	/// Read data at the given offset into multiple buffers.
+	///
+	/// The buffers are filled in order, and the file position of the [`MemFile`] is not changed.
+	///
+	/// Returns the total number of bytes read, which may be less than the total size of the buffers.
+	pub fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> std::io::Result<usize> {
+		sys::preadv(self.as_raw_fd(), bufs, offset)
+	}
+
+	/// Write data from multiple buffers at the given offset.
+	///
+	/// The buffers are written in order, and the file position of the [`MemFile`] is not changed.
+	///
+	/// Returns the total number of bytes written, which may be less than the total size of the buffers.
+	pub fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> std::io::Result<usize> {
+		let len = self.limit_write(total_len(bufs), || Ok(offset))?;
+		let result = sys::pwritev(self.as_raw_fd(), &truncate(bufs, len), offset)
+			.map_err(|e| self.diagnose_write_error(e));
+		self.update_accounting();
+		result
+	}
+
+	/// Read data at the given offset into multiple buffers, with additional flags.
+	///
+	/// This is identical to [`Self::read_vectored_at`], except that it uses `preadv2` to pass additional flags to the kernel.
+	/// For example, [`RwFlags::NOWAIT`] can be used to avoid blocking if the data is not readily available,
+	/// such as when the pages of the file have been swapped out.
+	///
+	/// Not all flags are supported by all kernel versions.
+	/// If a flag is not supported, the kernel returns an error of kind [`std::io::ErrorKind::Unsupported`].
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub fn read_vectored_at_with_flags(&self, bufs: &mut [IoSliceMut<'_>], offset: u64, flags: RwFlags) -> std::io::Result<usize> {
+		sys::preadv2(self.as_raw_fd(), bufs, offset, flags.bits())
+	}
+
+	/// Write data from multiple buffers at the given offset, with additional flags.
+	///
+	/// This is identical to [`Self::write_vectored_at`], except that it uses `pwritev2` to pass additional flags to the kernel.
+	///
+	/// Not all flags are supported by all kernel versions.
+	/// If a flag is not supported, the kernel returns an error of kind [`std::io::ErrorKind::Unsupported`].
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub fn write_vectored_at_with_flags(&self, bufs: &[IoSlice<'_>], offset: u64, flags: RwFlags) -> std::io::Result<usize> {
+		let len = self.limit_write(total_len(bufs), || {
+			if flags.contains(RwFlags::APPEND) {
+				Ok(self.metadata()?.len())
+			} else {
+				Ok(offset)
+			}
+		})?;
+		let result = sys::pwritev2(self.as_raw_fd(), &truncate(bufs, len), offset, flags.bits())
+			.map_err(|e| self.diagnose_write_error(e));
+		self.update_accounting();
+		result
+	}
+}
+
+/// Get the total length of all buffers.
+pub(crate) fn total_len(bufs: &[IoSlice<'_>]) -> usize {
+	bufs.iter().fold(0, |total, buf| total.saturating_add(buf.len()))
+}
+
+/// Limit the total length of the buffers to `len` bytes.
+///
+/// The buffers are only copied if they need to be shortened.
+pub(crate) fn truncate<'a>(bufs: &'a [IoSlice<'a>], len: usize) -> Cow<'a, [IoSlice<'a>]> {
+	if total_len(bufs) <= len {
+		return Cow::Borrowed(bufs);
+	}
+	let mut remaining = len;
+	let mut truncated = Vec::new();
+	for buf in bufs {
+		if remaining == 0 {
+			break;
+		}
+		let take = buf.len().min(remaining);
+		truncated.push(IoSlice::new(&buf[..take]));
+		remaining -= take;
+	}
+	Cow::Owned(truncated)
+}
+
+/// Flags for [`MemFile::read_vectored_at_with_flags`] and [`MemFile::write_vectored_at_with_flags`].
+///
+/// Flags can be combined with the `|` operator.
+/// See the documentation of `preadv2` for the meaning of each flag.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RwFlags {
+	bits: std::os::raw::c_int,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl RwFlags {
+	/// High priority read or write for polling block devices.
+	pub const HIPRI: Self = Self::from_bits(libc::RWF_HIPRI);
+
+	/// Per-operation equivalent of the `O_DSYNC` flag.
+	pub const DSYNC: Self = Self::from_bits(libc::RWF_DSYNC);
+
+	/// Per-operation equivalent of the `O_SYNC` flag.
+	pub const SYNC: Self = Self::from_bits(libc::RWF_SYNC);
+
+	/// Do not wait for data which is not immediately available.
+	///
+	/// If no data can be transferred without blocking, the operation fails with [`std::io::ErrorKind::WouldBlock`].
+	pub const NOWAIT: Self = Self::from_bits(libc::RWF_NOWAIT);
+
+	/// Per-operation equivalent of the `O_APPEND` flag.
+	///
+	/// The offset is ignored and data is appended to the end of the file.
+	pub const APPEND: Self = Self::from_bits(libc::RWF_APPEND);
+
+	#[inline]
+	const fn from_bits(bits: std::os::raw::c_int) -> Self {
+		Self { bits }
+	}
+
+	/// Get an empty set of flags.
+	#[inline]
+	pub const fn empty() -> Self {
+		Self::from_bits(0)
+	}
+
+	/// Get the flags as raw bitmask.
+	#[inline]
+	pub const fn bits(self) -> std::os::raw::c_int {
+		self.bits
+	}
+
+	/// Check if the set contains all of the given flags.
+	#[inline]
+	pub const fn contains(self, other: Self) -> bool {
+		self.bits & other.bits == other.bits
+	}
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl std::ops::BitOr for RwFlags {
+	type Output = Self;
+
+	#[inline]
+	fn bitor(self, right: Self) -> Self {
+		Self::from_bits(self.bits | right.bits)
+	}
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl std::ops::BitOrAssign for RwFlags {
+	#[inline]
+	fn bitor_assign(&mut self, right: Self) {
+		self.bits |= right.bits;
+	}
+}
@@ -0,0 +1,458 @@
+//! Buffer management for Wayland `wl_shm` pools backed by a [`MemFile`].
+//!
+//! This module does not depend on a Wayland client library.
+//! It only manages the memory of the pool and the layout of the buffers in it.
+//! You pass the file descriptor of the pool to `wl_shm.create_pool`,
+//! and use the offset, size, stride and format of each [`Buffer`] to create the matching `wl_buffer` objects.
+//!
+//! The pool file is sealed with [`Seal::Shrink`], because the pool must never shrink while the compositor has it mapped.
+//! Accessing the truncated part of the mapping would raise `SIGBUS` in the compositor.
+//! When [`ShmPool::allocate()`] grows the pool, you must send `wl_shm_pool.resize` with the new [`ShmPool::len()`]
+//! before attaching a buffer from the new region.
+//!
+//! # Example
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use memfile::wayland::{Format, ShmPool};
+//!
+//! let mut pool = ShmPool::new("window", 4096)?;
+//! let mut ring = pool.allocate_ring(2, 64, 64, 64 * 4, Format::Argb8888)?;
+//!
+//! // Draw into a buffer that is not in use by the compositor.
+//! let buffer = ring.next_available(&pool).unwrap();
+//! pool.pixels_mut(&buffer).fill(0xFF);
+//!
+//! // Attach and commit the buffer, then mark it busy until the compositor sends `wl_buffer.release`.
+//! pool.mark_busy(&buffer);
+//! assert!(ring.next_available(&pool) != Some(buffer));
+//! pool.release(&buffer);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ops::Range;
+
+use crate::mapping::RawMapping;
+use crate::{CreateOptions, Error, MemFile, Seal};
+
+/// Alignment of buffers in the pool.
+const BUFFER_ALIGN: u64 = 64;
+
+/// The maximum size of a pool, since `wl_shm` uses a signed 32 bit integer for the size.
+const MAX_POOL_SIZE: u64 = i32::MAX as u64;
+
+/// A pixel format for `wl_shm` buffers.
+///
+/// Only the formats that every compositor must support and some common formats are included.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Format {
+	/// 32 bit ARGB with 8 bits per channel.
+	Argb8888,
+
+	/// 32 bit RGB with 8 bits per channel, where the upper 8 bits are ignored.
+	Xrgb8888,
+
+	/// 32 bit ABGR with 8 bits per channel.
+	Abgr8888,
+
+	/// 32 bit BGR with 8 bits per channel, where the upper 8 bits are ignored.
+	Xbgr8888,
+
+	/// 24 bit RGB with 8 bits per channel.
+	Rgb888,
+
+	/// 16 bit RGB with 5 bits for red and blue, and 6 bits for green.
+	Rgb565,
+}
+
+impl Format {
+	/// Get the value of the format in the `wl_shm.format` enum.
+	///
+	/// Apart from [`Self::Argb8888`] and [`Self::Xrgb8888`], these are the DRM fourcc codes.
+	pub fn wl_shm_format(self) -> u32 {
+		match self {
+			Self::Argb8888 => 0,
+			Self::Xrgb8888 => 1,
+			Self::Abgr8888 => fourcc(*b"AB24"),
+			Self::Xbgr8888 => fourcc(*b"XB24"),
+			Self::Rgb888 => fourcc(*b"RG24"),
+			Self::Rgb565 => fourcc(*b"RG16"),
+		}
+	}
+
+	/// Get the number of bytes used for each pixel.
+	pub fn bytes_per_pixel(self) -> u32 {
+		match self {
+			Self::Argb8888 | Self::Xrgb8888 | Self::Abgr8888 | Self::Xbgr8888 => 4,
+			Self::Rgb888 => 3,
+			Self::Rgb565 => 2,
+		}
+	}
+}
+
+/// Get the DRM fourcc code for a format.
+fn fourcc(code: [u8; 4]) -> u32 {
+	u32::from_le_bytes(code)
+}
+
+/// A pixel buffer allocated from a [`ShmPool`].
+///
+/// This is a small handle that describes the location and layout of the buffer.
+/// Use it to create a `wl_buffer` with `wl_shm_pool.create_buffer`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Buffer {
+	slot: usize,
+	generation: u64,
+	offset: u64,
+	width: u32,
+	height: u32,
+	stride: u32,
+	format: Format,
+}
+
+impl Buffer {
+	/// Get the offset of the buffer in the pool.
+	pub fn offset(&self) -> u64 {
+		self.offset
+	}
+
+	/// Get the width of the buffer in pixels.
+	pub fn width(&self) -> u32 {
+		self.width
+	}
+
+	/// Get the height of the buffer in pixels.
+	pub fn height(&self) -> u32 {
+		self.height
+	}
+
+	/// Get the number of bytes between the start of two consecutive rows.
+	pub fn stride(&self) -> u32 {
+		self.stride
+	}
+
+	/// Get the pixel format of the buffer.
+	pub fn format(&self) -> Format {
+		self.format
+	}
+
+	/// Get the size of the buffer in bytes.
+	pub fn len(&self) -> u64 {
+		u64::from(self.stride) * u64::from(self.height)
+	}
+
+	/// Check if the buffer has a size of zero bytes.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Get the byte range of the buffer in the pool.
+	fn range(&self) -> Range<usize> {
+		self.offset as usize..(self.offset + self.len()) as usize
+	}
+}
+
+/// An allocated buffer in the pool.
+#[derive(Debug)]
+struct Slot {
+	buffer: Buffer,
+	busy: bool,
+}
+
+/// A `wl_shm` pool that sub-allocates pixel buffers from a single [`MemFile`].
+///
+/// See the [module documentation][self] for more information.
+#[derive(Debug)]
+pub struct ShmPool {
+	file: MemFile,
+	mapping: RawMapping,
+	len: u64,
+	slots: Vec<Option<Slot>>,
+	free: Vec<Range<u64>>,
+	generation: u64,
+}
+
+impl ShmPool {
+	/// Create a new pool with the given initial size in bytes.
+	///
+	/// The pool file is created with sealing support and sealed with [`Seal::Shrink`].
+	pub fn new(name: &str, size: u64) -> Result<Self, Error> {
+		if size > MAX_POOL_SIZE {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "pool size exceeds the limit of wl_shm").into());
+		}
+		let file = MemFile::create(name, CreateOptions::new().allow_sealing(true))?;
+		file.set_len(size)?;
+		file.add_seal(Seal::Shrink)?;
+		let mapping = RawMapping::new(file.as_fd(), size as usize, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED)?;
+		let mut pool = Self {
+			file,
+			mapping,
+			len: size,
+			slots: Vec::new(),
+			free: Vec::new(),
+			generation: 0,
+		};
+		if size > 0 {
+			pool.insert_free(0..size);
+		}
+		Ok(pool)
+	}
+
+	/// Get the [`MemFile`] that backs the pool.
+	///
+	/// Pass the file descriptor to `wl_shm.create_pool` to create the pool in the compositor.
+	pub fn file(&self) -> &MemFile {
+		&self.file
+	}
+
+	/// Get the current size of the pool in bytes.
+	///
+	/// If this changes after [`Self::allocate()`], you must send `wl_shm_pool.resize` with the new size.
+	pub fn len(&self) -> u64 {
+		self.len
+	}
+
+	/// Check if the pool has a size of zero bytes.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Allocate a new buffer from the pool.
+	///
+	/// The stride must be at least `width * format.bytes_per_pixel()`.
+	/// If there is not enough free space, the pool is grown.
+	///
+	/// The contents of a new buffer are unspecified, since the memory may have been used by a freed buffer before.
+	pub fn allocate(&mut self, width: u32, height: u32, stride: u32, format: Format) -> std::io::Result<Buffer> {
+		let min_stride = u64::from(width) * u64::from(format.bytes_per_pixel());
+		if u64::from(stride) < min_stride {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "stride is too small for the width and format of the buffer"));
+		}
+		let len = u64::from(stride) * u64::from(height);
+		let offset = match self.find_free(len) {
+			Some(offset) => offset,
+			None => {
+				self.grow(len)?;
+				self.find_free(len).unwrap()
+			},
+		};
+		self.take_free(offset..offset + len);
+
+		self.generation += 1;
+		let slot = self.slots.iter().position(|slot| slot.is_none()).unwrap_or(self.slots.len());
+		let buffer = Buffer {
+			slot,
+			generation: self.generation,
+			offset,
+			width,
+			height,
+			stride,
+			format,
+		};
+		let entry = Some(Slot { buffer, busy: false });
+		if slot == self.slots.len() {
+			self.slots.push(entry);
+		} else {
+			self.slots[slot] = entry;
+		}
+		Ok(buffer)
+	}
+
+	/// Allocate a ring of buffers with the same layout, for double or triple buffering.
+	pub fn allocate_ring(&mut self, count: usize, width: u32, height: u32, stride: u32, format: Format) -> std::io::Result<BufferRing> {
+		let mut buffers = Vec::with_capacity(count);
+		for _ in 0..count {
+			match self.allocate(width, height, stride, format) {
+				Ok(buffer) => buffers.push(buffer),
+				Err(e) => {
+					for buffer in &buffers {
+						self.free(buffer);
+					}
+					return Err(e);
+				},
+			}
+		}
+		Ok(BufferRing { buffers, next: 0 })
+	}
+
+	/// Free a buffer so the memory can be reused.
+	///
+	/// You should only free a buffer after destroying the matching `wl_buffer`.
+	/// Freeing a buffer that was already freed has no effect.
+	pub fn free(&mut self, buffer: &Buffer) {
+		if self.slot_mut(buffer).is_none() {
+			return;
+		}
+		self.slots[buffer.slot] = None;
+		if !buffer.is_empty() {
+			self.insert_free(buffer.offset..buffer.offset + buffer.len());
+		}
+	}
+
+	/// Mark a buffer as busy, because it has been attached to a surface and committed.
+	///
+	/// The buffer remains busy until you call [`Self::release()`] when the compositor sends `wl_buffer.release`.
+	pub fn mark_busy(&mut self, buffer: &Buffer) {
+		if let Some(slot) = self.slot_mut(buffer) {
+			slot.busy = true;
+		}
+	}
+
+	/// Mark a buffer as released by the compositor.
+	pub fn release(&mut self, buffer: &Buffer) {
+		if let Some(slot) = self.slot_mut(buffer) {
+			slot.busy = false;
+		}
+	}
+
+	/// Check if a buffer is in use by the compositor.
+	///
+	/// Buffers that have been freed are never busy.
+	pub fn is_busy(&self, buffer: &Buffer) -> bool {
+		self.slot(buffer).is_some_and(|slot| slot.busy)
+	}
+
+	/// Get the pixel data of a buffer.
+	///
+	/// # Panics
+	/// This function panics if the buffer does not belong to this pool or if it has been freed.
+	pub fn pixels(&self, buffer: &Buffer) -> &[u8] {
+		assert!(self.slot(buffer).is_some(), "buffer does not belong to a live slot of the pool");
+		&self.data()[buffer.range()]
+	}
+
+	/// Get the pixel data of a buffer for drawing.
+	///
+	/// You should not modify a buffer while it is [busy][Self::is_busy], since the compositor may be reading it.
+	///
+	/// # Panics
+	/// This function panics if the buffer does not belong to this pool or if it has been freed.
+	pub fn pixels_mut(&mut self, buffer: &Buffer) -> &mut [u8] {
+		assert!(self.slot(buffer).is_some(), "buffer does not belong to a live slot of the pool");
+		&mut self.data_mut()[buffer.range()]
+	}
+
+	/// Get the slot of a buffer if it has not been freed.
+	fn slot(&self, buffer: &Buffer) -> Option<&Slot> {
+		match self.slots.get(buffer.slot) {
+			Some(Some(slot)) if slot.buffer == *buffer => Some(slot),
+			_ => None,
+		}
+	}
+
+	/// Get the slot of a buffer if it has not been freed.
+	fn slot_mut(&mut self, buffer: &Buffer) -> Option<&mut Slot> {
+		match self.slots.get_mut(buffer.slot) {
+			Some(Some(slot)) if slot.buffer == *buffer => Some(slot),
+			_ => None,
+		}
+	}
+
+	/// Get the mapped memory of the pool.
+	fn data(&self) -> &[u8] {
+		// The file is sealed against shrinking, so the whole mapping stays valid.
+		unsafe { std::slice::from_raw_parts(self.mapping.as_ptr(), self.mapping.len()) }
+	}
+
+	/// Get the mapped memory of the pool.
+	fn data_mut(&mut self) -> &mut [u8] {
+		// The file is sealed against shrinking, so the whole mapping stays valid.
+		unsafe { std::slice::from_raw_parts_mut(self.mapping.as_ptr(), self.mapping.len()) }
+	}
+
+	/// Find the offset of the first free range that can hold `len` bytes.
+	fn find_free(&self, len: u64) -> Option<u64> {
+		self.free.iter().find_map(|range| {
+			let start = align_up(range.start)?;
+			let end = start.checked_add(len)?;
+			(end <= range.end).then_some(start)
+		})
+	}
+
+	/// Remove a range from the free list.
+	///
+	/// The range must be fully contained in a free range.
+	fn take_free(&mut self, taken: Range<u64>) {
+		if taken.is_empty() {
+			return;
+		}
+		let index = self.free.iter().position(|range| range.start <= taken.start && taken.end <= range.end).unwrap();
+		let range = self.free.remove(index);
+		if taken.end < range.end {
+			self.free.insert(index, taken.end..range.end);
+		}
+		if range.start < taken.start {
+			self.free.insert(index, range.start..taken.start);
+		}
+	}
+
+	/// Add a range to the free list, merging it with adjacent ranges.
+	fn insert_free(&mut self, mut range: Range<u64>) {
+		let index = self.free.partition_point(|free| free.start < range.start);
+		if index < self.free.len() && self.free[index].start == range.end {
+			range.end = self.free.remove(index).end;
+		}
+		if index > 0 && self.free[index - 1].end == range.start {
+			self.free[index - 1].end = range.end;
+		} else {
+			self.free.insert(index, range);
+		}
+	}
+
+	/// Grow the pool so that it has a free range of at least `len` bytes at the end.
+	fn grow(&mut self, len: u64) -> std::io::Result<()> {
+		let free_tail = match self.free.last() {
+			Some(range) if range.end == self.len => range.start,
+			_ => self.len,
+		};
+		let needed = align_up(free_tail)
+			.and_then(|start| start.checked_add(len))
+			.filter(|&needed| needed <= MAX_POOL_SIZE)
+			.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::OutOfMemory, "buffer does not fit in the maximum pool size of wl_shm"))?;
+		let new_len = needed.max(self.len.saturating_mul(2)).min(MAX_POOL_SIZE);
+
+		self.file.set_len(new_len)?;
+		self.mapping.remap(self.file.as_fd(), new_len as usize, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED)?;
+		let old_len = std::mem::replace(&mut self.len, new_len);
+		self.insert_free(old_len..new_len);
+		Ok(())
+	}
+}
+
+/// Round an offset up to the buffer alignment.
+fn align_up(offset: u64) -> Option<u64> {
+	Some(offset.checked_add(BUFFER_ALIGN - 1)? & !(BUFFER_ALIGN - 1))
+}
+
+/// A fixed set of buffers with the same layout, used for double or triple buffering.
+///
+/// Created with [`ShmPool::allocate_ring()`].
+#[derive(Debug, Clone)]
+pub struct BufferRing {
+	buffers: Vec<Buffer>,
+	next: usize,
+}
+
+impl BufferRing {
+	/// Get the next buffer that is not in use by the compositor.
+	///
+	/// Buffers are handed out in round-robin order, skipping busy buffers.
+	/// Returns `None` if all buffers are busy.
+	pub fn next_available(&mut self, pool: &ShmPool) -> Option<Buffer> {
+		for i in 0..self.buffers.len() {
+			let index = (self.next + i) % self.buffers.len();
+			let buffer = self.buffers[index];
+			if !pool.is_busy(&buffer) {
+				self.next = (index + 1) % self.buffers.len();
+				return Some(buffer);
+			}
+		}
+		None
+	}
+
+	/// Get all buffers in the ring.
+	pub fn buffers(&self) -> &[Buffer] {
+		&self.buffers
+	}
+}
@@ -0,0 +1,26 @@
+#![cfg(feature = "allocator-api2")]
+
+use assert2::{assert, let_assert};
+use allocator_api2::vec::Vec;
+use memfile::MemFileArena;
+
+#[test]
+fn arena_vec() {
+	let_assert!(Ok(arena) = MemFileArena::create("arena", 64 * 1024));
+	let mut values = Vec::new_in(&arena);
+	values.extend(0..100_u32);
+	assert!(arena.used() >= 400);
+
+	// The elements live in the file, so another mapping can read them through a handle.
+	let_assert!(Some(handle) = arena.to_shm_ptr(values.as_ptr().cast::<[u32; 100]>()));
+	let_assert!(Ok(file) = arena.file().try_clone());
+	let_assert!(Ok(other) = MemFileArena::attach(file));
+	let_assert!(Some(ptr) = other.resolve(handle));
+	let copy = unsafe { ptr.as_ref() };
+	assert!(copy.iter().copied().eq(0..100));
+
+	// The vector was the last allocation, so its memory is reclaimed when it is dropped.
+	let used = arena.used();
+	drop(values);
+	assert!(arena.used() < used);
+}
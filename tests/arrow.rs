@@ -0,0 +1,61 @@
+#![cfg(feature = "arrow")]
+
+use arrow_array::{Int64Array, RecordBatch, StringArray};
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use assert2::{assert, let_assert};
+use memfile::arrow::{write_stream, MappedStreamReader};
+use memfile::{Error, MemFile, Seal};
+use std::io::Write;
+use std::sync::Arc;
+
+#[test]
+fn write_and_read_stream() {
+	let schema = Arc::new(Schema::new(vec![
+		Field::new("id", DataType::Int64, false),
+		Field::new("name", DataType::Utf8, true),
+	]));
+	let_assert!(Ok(first) = RecordBatch::try_new(schema.clone(), vec![
+		Arc::new(Int64Array::from(vec![1, 2, 3])),
+		Arc::new(StringArray::from(vec![Some("a"), None, Some("c")])),
+	]));
+	let_assert!(Ok(second) = RecordBatch::try_new(schema.clone(), vec![
+		Arc::new(Int64Array::from(vec![4])),
+		Arc::new(StringArray::from(vec!["d"])),
+	]));
+
+	let_assert!(Ok(file) = write_stream("batches", &schema, [&first, &second]));
+	let_assert!(Ok(seals) = file.get_seals());
+	assert!(seals == Seal::Write | Seal::Shrink | Seal::Grow);
+
+	let_assert!(Ok(reader) = MappedStreamReader::new(&file));
+	assert!(reader.schema() == schema);
+	let_assert!(Ok(batches) = reader.collect::<Result<Vec<_>, _>>());
+	assert!(batches == [first, second]);
+
+	// The arrays keep the mapping alive after the file and reader are gone.
+	drop(file);
+	let_assert!(Some(ids) = batches[1].column(0).as_any().downcast_ref::<Int64Array>());
+	assert!(ids.value(0) == 4);
+}
+
+#[test]
+fn empty_stream() {
+	let schema = Schema::new(vec![Field::new("id", DataType::Int64, false)]);
+	let_assert!(Ok(file) = write_stream("empty", &schema, []));
+	let_assert!(Ok(mut reader) = MappedStreamReader::new(&file));
+	assert!(*reader.schema() == schema);
+	assert!(let None = reader.next());
+}
+
+#[test]
+fn reader_requires_seals() {
+	let_assert!(Ok(mut file) = MemFile::create_sealable("unsealed"));
+	assert!(let Ok(()) = file.write_all(b"not an arrow stream"));
+	let_assert!(Err(ArrowError::ExternalError(error)) = MappedStreamReader::new(&file));
+	let_assert!(Some(Error::MissingSeals(missing)) = error.downcast_ref::<Error>());
+	assert!(*missing == Seal::Write | Seal::Shrink | Seal::Grow);
+
+	// Garbage in a sealed file is rejected.
+	assert!(let Ok(()) = file.add_seals(Seal::Write | Seal::Shrink | Seal::Grow));
+	assert!(let Err(_) = MappedStreamReader::new(&file));
+}
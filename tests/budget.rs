@@ -0,0 +1,75 @@
+use assert2::{assert, let_assert};
+use memfile::{budget, CreateOptions, Error, MemFile, Seal};
+use std::io::Write;
+use std::os::unix::fs::FileExt;
+
+// The budget is global, so all checks are done in a single test.
+#[test]
+fn process_budget() {
+	assert!(budget::limit() == None);
+	assert!(budget::used() == 0);
+
+	// Files are charged for their size, and clones share the charge.
+	let_assert!(Ok(mut a) = MemFile::create_default("a"));
+	assert!(let Ok(()) = a.set_len(1000));
+	assert!(budget::used() == 1000);
+	let_assert!(Ok(clone) = a.try_clone());
+	assert!(let Ok(()) = clone.set_len(500));
+	assert!(budget::used() == 500);
+
+	budget::set_limit(2000);
+	assert!(budget::limit() == Some(2000));
+	let_assert!(Ok(b) = MemFile::create("b", CreateOptions::new().allow_sealing(true)));
+	assert!(let Ok(()) = b.set_len(1500));
+	assert!(budget::used() == 2000);
+
+	// Growing or creating files beyond the budget fails.
+	let_assert!(Err(Error::BudgetExceeded) = b.set_len(1501));
+	let_assert!(Err(error) = a.write_all(&[0; 600]));
+	assert!(error.kind() == std::io::ErrorKind::QuotaExceeded);
+	let_assert!(Err(Error::BudgetExceeded) = MemFile::create_default("c"));
+
+	// Shrinking and dropping files returns bytes to the budget.
+	assert!(let Ok(()) = b.set_len(1000));
+	assert!(budget::used() == 1500);
+	drop(a);
+	assert!(budget::used() == 1500);
+	drop(clone);
+	assert!(budget::used() == 1000);
+	let_assert!(Ok(c) = MemFile::create_default("c"));
+	assert!(let Ok(()) = c.set_len(1000));
+	assert!(budget::used() == 2000);
+
+	budget::set_limit(None);
+	assert!(let Ok(()) = c.set_len(10_000));
+	assert!(budget::used() == 11_000);
+
+	// Failed writes do not consume the budget.
+	let_assert!(Ok(d) = MemFile::create_sealable("d"));
+	assert!(let Ok(()) = d.add_seals(Seal::Write.into()));
+	let_assert!(Err(error) = d.write_at(b"hello", 1 << 30));
+	assert!(error.kind() == std::io::ErrorKind::PermissionDenied);
+	assert!(budget::used() == 11_000);
+	let_assert!(Err(_) = d.write_vectored_at(&[std::io::IoSlice::new(b"hello")], 1 << 30));
+	assert!(budget::used() == 11_000);
+	let_assert!(Err(_) = d.copy_from_fd(&c, 1 << 30..(1 << 30) + 5));
+	assert!(budget::used() == 11_000);
+	drop(d);
+	drop(b);
+	drop(c);
+	assert!(budget::used() == 0);
+
+	// Files converted into a raw file descriptor keep their charge, even after the file descriptor is closed.
+	let_assert!(Ok(e) = MemFile::create_default("e"));
+	assert!(let Ok(()) = e.set_len(1000));
+	let_assert!(Ok(clone) = e.try_clone());
+	let fd = e.into_fd();
+	assert!(budget::used() == 1000);
+	drop(fd);
+	drop(clone);
+	assert!(budget::used() == 1000);
+
+	budget::set_limit(1000);
+	let_assert!(Err(Error::BudgetExceeded) = MemFile::create_default("f"));
+	budget::set_limit(None);
+}
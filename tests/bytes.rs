@@ -0,0 +1,33 @@
+#![cfg(feature = "bytes")]
+
+use assert2::{assert, let_assert};
+use memfile::{Error, MemFile, Seal, Seals};
+use std::io::Write;
+
+#[test]
+fn into_bytes() {
+	let_assert!(Ok(mut file) = MemFile::create_sealable("payload"));
+	assert!(let Ok(()) = file.write_all(b"Hello world!"));
+	let_assert!(Ok(clone) = file.try_clone());
+	let_assert!(Err(Error::MissingSeals(missing)) = clone.into_bytes());
+	assert!(missing == Seal::Write | Seal::Shrink);
+
+	assert!(let Ok(()) = file.add_seals(Seal::Write | Seal::Shrink));
+	let_assert!(Ok(bytes) = file.into_bytes());
+	assert!(bytes == b"Hello world!"[..]);
+
+	// Slices and clones share the mapping, which outlives the original `Bytes`.
+	let world = bytes.slice(6..11);
+	let clone = bytes.clone();
+	drop(bytes);
+	assert!(world == b"world"[..]);
+	assert!(clone.len() == 12);
+}
+
+#[test]
+fn into_bytes_empty() {
+	let_assert!(Ok(file) = MemFile::create_sealable("empty"));
+	assert!(let Ok(()) = file.add_seals(Seals::all()));
+	let_assert!(Ok(bytes) = file.into_bytes());
+	assert!(bytes.is_empty());
+}
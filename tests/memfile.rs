@@ -154,3 +154,976 @@ fn sealing_must_be_enabled() {
 	let_assert!(Err(error) = original.add_seals(Seals::all()));
 	assert!(error.kind() == std::io::ErrorKind::PermissionDenied);
 }
+
+#[test]
+fn capabilities() {
+	let capabilities = memfile::Capabilities::detect();
+	assert!(capabilities == memfile::Capabilities::detect());
+	assert!(capabilities.memfd_create());
+	assert!(capabilities.allow_sealing());
+
+	// The reported support for future write seals should match reality.
+	#[cfg(target_os = "linux")]
+	{
+		let_assert!(Ok(file) = MemFile::create_sealable("foo"));
+		assert!(file.add_seal(Seal::FutureWrite).is_ok() == capabilities.future_write());
+	}
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn secret_mem_file() {
+	use memfile::SecretMemFile;
+
+	let mut secret = match SecretMemFile::create(64) {
+		Ok(x) => x,
+		Err(e) if e.kind() == std::io::ErrorKind::Unsupported => {
+			eprintln!("memfd_secret is not supported, skipping test");
+			return;
+		},
+		Err(e) => panic!("failed to create secret memory: {e}"),
+	};
+	assert!(secret.len() == 64);
+	assert!(secret.as_slice() == [0u8; 64]);
+
+	secret.as_mut_slice()[..12].copy_from_slice(b"Hello world!");
+	assert!(&secret.as_slice()[..12] == b"Hello world!");
+	assert!(format!("{secret:?}") == "SecretMemFile { len: 64, .. }");
+}
+
+#[test]
+fn copy_between_memfiles() {
+	let_assert!(Ok(mut source) = MemFile::create_default("source"));
+	assert!(let Ok(()) = source.write_all(b"Hello world!"));
+	assert!(let Ok(0) = source.seek(std::io::SeekFrom::Start(0)));
+
+	let_assert!(Ok(mut target) = MemFile::create_default("target"));
+	assert!(let Ok(12) = target.copy_from_fd(&source, 4..100));
+	assert!(let Ok(12) = source.stream_position());
+	assert!(let Ok(0) = target.stream_position());
+
+	let mut buffer = Vec::new();
+	assert!(let Ok(16) = target.read_to_end(&mut buffer));
+	assert!(buffer == b"\0\0\0\0Hello world!");
+
+	let_assert!(Ok(mut copy) = MemFile::create_default("copy"));
+	assert!(let Ok(5) = target.copy_to_fd(&copy, 4..9));
+	assert!(let Ok(5) = copy.stream_position());
+	assert!(let Ok(0) = copy.seek(std::io::SeekFrom::Start(0)));
+	let mut buffer = Vec::new();
+	assert!(let Ok(5) = copy.read_to_end(&mut buffer));
+	assert!(buffer == b"Hello");
+}
+
+#[test]
+fn copy_through_socket() {
+	use std::os::unix::net::UnixStream;
+
+	let_assert!(Ok((a, mut b)) = UnixStream::pair());
+	let_assert!(Ok(source) = MemFile::create_default("source"));
+	assert!(let Ok(()) = std::os::unix::fs::FileExt::write_all_at(&source, b"Hello world!", 0));
+	assert!(let Ok(12) = source.copy_to_fd(&a, 0..12));
+
+	let mut buffer = [0; 12];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"Hello world!");
+
+	assert!(let Ok(()) = b.write_all(b"Goodbye!"));
+	let_assert!(Ok(target) = MemFile::create_default("target"));
+	assert!(let Ok(8) = target.copy_from_fd(&a, 2..10));
+	let mut buffer = [0; 10];
+	assert!(let Ok(()) = std::os::unix::fs::FileExt::read_exact_at(&target, &mut buffer, 0));
+	assert!(&buffer == b"\0\0Goodbye!");
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn copy_through_pipe() {
+	use std::os::fd::{FromRawFd, OwnedFd};
+
+	let mut fds = [0; 2];
+	assert!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } == 0);
+	let read_end = unsafe { OwnedFd::from_raw_fd(fds[0]) };
+	let write_end = unsafe { OwnedFd::from_raw_fd(fds[1]) };
+
+	let_assert!(Ok(source) = MemFile::create_default("source"));
+	assert!(let Ok(()) = std::os::unix::fs::FileExt::write_all_at(&source, b"Hello world!", 0));
+	assert!(let Ok(12) = source.copy_to_fd(&write_end, 0..100));
+	drop(write_end);
+
+	let_assert!(Ok(target) = MemFile::create_default("target"));
+	assert!(let Ok(12) = target.copy_from_fd(&read_end, 0..100));
+	let mut buffer = [0; 12];
+	assert!(let Ok(()) = std::os::unix::fs::FileExt::read_exact_at(&target, &mut buffer, 0));
+	assert!(&buffer == b"Hello world!");
+}
+
+#[test]
+fn vectored_io() {
+	use std::io::{IoSlice, IoSliceMut};
+
+	let_assert!(Ok(mut file) = MemFile::create_default("foo"));
+	assert!(let Ok(12) = file.write_vectored(&[IoSlice::new(b"Hello"), IoSlice::new(b" world!")]));
+	assert!(let Ok(8) = file.write_vectored_at(&[IoSlice::new(b"Good"), IoSlice::new(b"bye!")], 12));
+
+	let mut hello = [0; 5];
+	let mut world = [0; 7];
+	assert!(let Ok(0) = file.seek(std::io::SeekFrom::Start(0)));
+	assert!(let Ok(12) = file.read_vectored(&mut [IoSliceMut::new(&mut hello), IoSliceMut::new(&mut world)]));
+	assert!(&hello == b"Hello");
+	assert!(&world == b" world!");
+
+	let mut good = [0; 4];
+	let mut bye = [0; 10];
+	assert!(let Ok(8) = file.read_vectored_at(&mut [IoSliceMut::new(&mut good), IoSliceMut::new(&mut bye)], 12));
+	assert!(&good == b"Good");
+	assert!(&bye[..4] == b"bye!");
+	assert!(let Ok(12) = file.stream_position());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn vectored_io_with_flags() {
+	use memfile::RwFlags;
+	use std::io::{IoSlice, IoSliceMut};
+
+	let_assert!(Ok(file) = MemFile::create_default("foo"));
+	assert!(let Ok(12) = file.write_vectored_at_with_flags(&[IoSlice::new(b"Hello world!")], 0, RwFlags::DSYNC));
+
+	let mut buffer = [0; 12];
+	match file.read_vectored_at_with_flags(&mut [IoSliceMut::new(&mut buffer)], 0, RwFlags::NOWAIT) {
+		Ok(read) => {
+			assert!(read == 12);
+			assert!(&buffer == b"Hello world!");
+		},
+		Err(e) => assert!(e.kind() == std::io::ErrorKind::Unsupported || e.kind() == std::io::ErrorKind::WouldBlock),
+	}
+}
+
+#[track_caller]
+fn temp_dir(name: &str) -> std::path::PathBuf {
+	let path = std::env::temp_dir().join(format!("memfile-test-{}-{name}", std::process::id()));
+	let _ = std::fs::remove_dir_all(&path);
+	let_assert!(Ok(()) = std::fs::create_dir_all(&path));
+	path
+}
+
+#[test]
+fn save_and_load() {
+	let dir = temp_dir("save-and-load");
+	let path = dir.join("state");
+
+	let_assert!(Ok(mut file) = MemFile::create_sealable("foo"));
+	assert!(let Ok(()) = file.write_all(b"Hello world!"));
+	assert!(let Ok(()) = file.add_seals(Seal::Shrink | Seal::Grow));
+	assert!(let Ok(()) = file.save_to(&path));
+	assert!(!dir.join("state.seals").exists());
+
+	let_assert!(Ok(mut loaded) = MemFile::load_from(&path, memfile::CreateOptions::new()));
+	let mut buffer = Vec::new();
+	assert!(let Ok(12) = loaded.read_to_end(&mut buffer));
+	assert!(buffer == b"Hello world!");
+	let_assert!(Ok(seals) = loaded.get_seals());
+	assert!(seals == Seals::from(Seal::Seal));
+
+	assert!(let Ok(()) = file.save_to_with_seals(&path));
+	let_assert!(Ok(seals) = std::fs::read_to_string(dir.join("state.seals")));
+	assert!(seals == "Shrink\nGrow\n");
+	let_assert!(Ok(loaded) = MemFile::load_from(&path, memfile::CreateOptions::new()));
+	let_assert!(Ok(seals) = loaded.get_seals());
+	assert!(seals == Seal::Shrink | Seal::Grow);
+	assert!(let Ok(12) = loaded.metadata().map(|x| x.len()));
+
+	// Saving without seals should remove the old seals file.
+	assert!(let Ok(()) = file.save_to(&path));
+	assert!(!dir.join("state.seals").exists());
+
+	// Loading a file that exceeds the maximum size should fail instead of truncating it.
+	let_assert!(Err(error) = MemFile::load_from(&path, memfile::CreateOptions::new().max_size(5)));
+	assert!(error.kind() == std::io::ErrorKind::FileTooLarge);
+	let_assert!(Ok(loaded) = MemFile::load_from(&path, memfile::CreateOptions::new().max_size(12)));
+	assert!(let Ok(12) = loaded.metadata().map(|x| x.len()));
+
+	let_assert!(Ok(entries) = std::fs::read_dir(&dir));
+	assert!(entries.count() == 1);
+	let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn default_backend() {
+	let_assert!(Ok(file) = MemFile::create_default("foo"));
+	assert!(file.backend() == memfile::Backend::MemfdCreate);
+	assert!(file.backend().supports_sealing());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn fallback_backends() {
+	use memfile::{Backend, CreateOptions};
+
+	for backend in [Backend::ShmOpen, Backend::TmpFile] {
+		let options = CreateOptions::new().backend(backend);
+		let_assert!(Ok(mut file) = MemFile::create("foo", options), "{backend:?}");
+		assert!(file.backend() == backend);
+		assert!(!file.backend().supports_sealing());
+
+		assert!(let Ok(()) = file.write_all(b"Hello world!"));
+		let mut buffer = [0u8; 12];
+		assert!(let Ok(0) = file.seek(std::io::SeekFrom::Start(0)));
+		assert!(let Ok(()) = file.read_exact(&mut buffer));
+		assert!(&buffer == b"Hello world!");
+
+		let_assert!(Err(error) = file.add_seal(Seal::Write));
+		assert!(error.kind() == std::io::ErrorKind::PermissionDenied);
+
+		let options = CreateOptions::new().huge_tlb(memfile::HugeTlb::Huge2MB).backend(backend);
+		let_assert!(Err(error) = MemFile::create("foo", options));
+		assert!(error.kind() == std::io::ErrorKind::InvalidInput);
+
+		let options = CreateOptions::new().allow_sealing(true).backend(backend);
+		let_assert!(Err(error) = MemFile::create("foo", options));
+		assert!(error.kind() == std::io::ErrorKind::InvalidInput);
+	}
+}
+
+#[test]
+fn structured_errors() {
+	use memfile::Error;
+
+	let_assert!(Err(Error::NameTooLong) = MemFile::create_default(&"a".repeat(250)));
+
+	let_assert!(Ok(file) = MemFile::create_default("foo"));
+	let_assert!(Err(Error::SealingNotAllowed) = file.add_seal(Seal::Write));
+
+	let_assert!(Ok(mut file) = MemFile::create_sealable("foo"));
+	assert!(let Ok(()) = file.set_len(12));
+	assert!(let Ok(()) = file.add_seals(Seal::Shrink | Seal::Write));
+	let_assert!(Err(Error::SealedAgainst(seals)) = file.set_len(6));
+	assert!(seals == Seals::from(Seal::Shrink));
+
+	// Errors from the standard I/O traits carry the diagnosis too.
+	let_assert!(Err(error) = file.write_all(b"Hello world!"));
+	assert!(error.kind() == std::io::ErrorKind::PermissionDenied);
+	let_assert!(Some(Error::SealedAgainst(seals)) = error.get_ref().and_then(|e| e.downcast_ref::<Error>()));
+	assert!(*seals == Seals::from(Seal::Write));
+
+	// And so do the errors from positional vectored writes.
+	let_assert!(Err(error) = file.write_vectored_at(&[std::io::IoSlice::new(b"Hello")], 0));
+	let_assert!(Some(Error::SealedAgainst(seals)) = error.get_ref().and_then(|e| e.downcast_ref::<Error>()));
+	assert!(*seals == Seals::from(Seal::Write));
+	#[cfg(target_os = "linux")]
+	{
+		let_assert!(Err(error) = file.write_vectored_at_with_flags(&[std::io::IoSlice::new(b"Hello")], 0, memfile::RwFlags::empty()));
+		let_assert!(Some(Error::SealedAgainst(seals)) = error.get_ref().and_then(|e| e.downcast_ref::<Error>()));
+		assert!(*seals == Seals::from(Seal::Write));
+	}
+
+	let_assert!(Err(error) = MemFile::from_fd(dup_stdout()));
+	let_assert!(Some(Error::NotAMemfd) = error.error().get_ref().and_then(|e| e.downcast_ref::<Error>()));
+}
+
+#[test]
+fn structured_error_writable_mapping() {
+	let_assert!(Ok(file) = MemFile::create_sealable("foo"));
+	assert!(let Ok(()) = file.set_len(4096));
+	let data = unsafe { libc::mmap(std::ptr::null_mut(), 4096, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, file.as_raw_fd(), 0) };
+	assert!(data != libc::MAP_FAILED);
+	let_assert!(Err(memfile::Error::WritableMappingExists) = file.add_seal(Seal::Write));
+	unsafe { libc::munmap(data, 4096) };
+	assert!(let Ok(()) = file.add_seal(Seal::Write));
+}
+
+#[test]
+fn name_validation() {
+	use memfile::{CreateOptions, Error, MemFileName};
+
+	const NAME: MemFileName = MemFileName::from_static(c"foo");
+	assert!(let Ok(_) = MemFile::create_named(&NAME, CreateOptions::new()));
+
+	let long = "é".repeat(200);
+	let_assert!(Err(Error::NameTooLong) = MemFileName::new(&long));
+	let_assert!(Err(Error::NameTooLong) = MemFile::create_default(&long));
+	let_assert!(Err(Error::NameContainsNul) = MemFile::create_default("foo\0bar"));
+	let_assert!(Err(Error::NameContainsNul) = MemFile::create("foo\0bar", CreateOptions::new().truncate_name(true)));
+
+	let_assert!(Ok(truncated) = MemFileName::new_truncated(&long));
+	assert!(truncated.as_cstr().to_bytes().len() == 248);
+	assert!(let Ok(_) = MemFile::create(&long, CreateOptions::new().truncate_name(true)));
+
+	let long = std::ffi::CString::new("a".repeat(300)).unwrap();
+	let_assert!(Err(Error::NameTooLong) = MemFile::create_cstr(&long, CreateOptions::new()));
+	assert!(MemFileName::from_cstr_truncated(&long).as_cstr().to_bytes().len() == MemFileName::MAX_LEN);
+	assert!(let Ok(_) = MemFile::create_cstr(&long, CreateOptions::new().truncate_name(true)));
+}
+
+#[test]
+fn pool() {
+	use memfile::{CreateOptions, MemFilePool};
+
+	let_assert!(Ok(pool) = MemFilePool::new("pool", CreateOptions::new().allow_sealing(true)));
+	let_assert!(Ok(mut file) = pool.get(100));
+	assert!(let Ok(100) = file.metadata().map(|x| x.len()));
+	assert!(let Ok(()) = file.write_all(b"Hello world!"));
+	let fd = file.as_raw_fd();
+	assert!(pool.put(file));
+
+	// The file should be reused, and it should be cleared.
+	let_assert!(Ok(mut file) = pool.get(1000));
+	assert!(file.as_raw_fd() == fd);
+	assert!(let Ok(1000) = file.metadata().map(|x| x.len()));
+	assert!(let Ok(0) = file.stream_position());
+	let mut buffer = [0xFF; 12];
+	assert!(let Ok(()) = file.read_exact(&mut buffer));
+	assert!(buffer == [0; 12]);
+
+	// A different size class should not reuse the file.
+	assert!(pool.put(file));
+	let_assert!(Ok(large) = pool.get(8192));
+	assert!(large.as_raw_fd() != fd);
+
+	// Sealed files should not be recycled.
+	assert!(let Ok(()) = large.add_seal(Seal::Grow));
+	assert!(!pool.put(large));
+
+	let stats = pool.stats();
+	assert!(stats.hits() == 1);
+	assert!(stats.misses() == 2);
+	assert!(stats.recycled() == 2);
+	assert!(stats.rejected() == 1);
+	assert!(stats.idle() == 1);
+}
+
+#[test]
+fn pool_options() {
+	use memfile::{CreateOptions, MemFilePool};
+
+	// Size classes are capped at the maximum size of the files.
+	let_assert!(Ok(pool) = MemFilePool::new("pool", CreateOptions::new().max_size(6000)));
+	let_assert!(Ok(file) = pool.get(5000));
+	let fd = file.as_raw_fd();
+	assert!(pool.put(file));
+	let_assert!(Ok(file) = pool.get(6000));
+	assert!(file.as_raw_fd() == fd);
+	assert!(let Ok(6000) = file.metadata().map(|x| x.len()));
+	assert!(pool.put(file));
+
+	// Files created with different options should not be recycled.
+	let_assert!(Ok(other) = MemFile::create("other", CreateOptions::new().max_size(8000)));
+	assert!(!pool.put(other));
+	let_assert!(Ok(other) = MemFile::create("other", CreateOptions::new().allow_sealing(true).max_size(6000)));
+	assert!(!pool.put(other));
+	let_assert!(Ok(other) = MemFile::create("other", CreateOptions::new().max_size(6000)));
+	assert!(pool.put(other));
+
+	let stats = pool.stats();
+	assert!(stats.hits() == 1);
+	assert!(stats.recycled() == 3);
+	assert!(stats.rejected() == 2);
+}
+
+#[test]
+fn append_log() {
+	use memfile::log::{AppendLog, LogReader};
+
+	let_assert!(Ok(mut log) = AppendLog::create("log"));
+	let_assert!(Ok(seals) = log.file().get_seals());
+	assert!(seals.contains(Seal::Shrink));
+
+	let_assert!(Ok(mut reader) = log.reader());
+	assert!(let Ok(None) = reader.next());
+	assert!(let Ok(16) = log.append(b"Hello"));
+	assert!(let Ok(25) = log.append(b""));
+	assert!(let Ok(Some(b"Hello")) = reader.next());
+	assert!(let Ok(Some(b"")) = reader.next());
+	assert!(let Ok(None) = reader.next());
+
+	// Grow the log beyond the initial mapping of the reader.
+	let big = vec![0xAB; 10_000];
+	for _ in 0..3 {
+		assert!(let Ok(_) = log.append(&big));
+	}
+	for _ in 0..3 {
+		let_assert!(Ok(Some(record)) = reader.next());
+		assert!(record == &big[..]);
+	}
+	assert!(let Ok(None) = reader.next());
+	assert!(reader.position() == log.committed_len());
+
+	// Readers require the shrink seal.
+	let_assert!(Ok(unsealed) = MemFile::create_sealable("log"));
+	let_assert!(Err(error) = LogReader::new(unsealed));
+	assert!(error.kind() == std::io::ErrorKind::InvalidInput);
+
+	// Readers require a valid header.
+	let_assert!(Ok(invalid) = MemFile::create_sealable("log"));
+	assert!(let Ok(()) = invalid.set_len(4096));
+	assert!(let Ok(()) = invalid.add_seal(Seal::Shrink));
+	let_assert!(Err(error) = LogReader::new(invalid));
+	assert!(error.kind() == std::io::ErrorKind::InvalidData);
+
+	// A truncated record is reported as invalid data.
+	let_assert!(Ok(mut truncated) = MemFile::create_sealable("log"));
+	assert!(let Ok(()) = truncated.write_all(b"MEMFLOG1"));
+	assert!(let Ok(()) = truncated.write_all(&17_u64.to_ne_bytes()));
+	assert!(let Ok(()) = truncated.write_all(&[5]));
+	assert!(let Ok(()) = truncated.add_seal(Seal::Shrink));
+	let_assert!(Ok(mut reader) = LogReader::new(truncated));
+	let_assert!(Err(error) = reader.next());
+	assert!(error.kind() == std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn growable_mapping() {
+	use memfile::{Error, GrowableMapping};
+
+	let_assert!(Ok(file) = MemFile::create_sealable("foo"));
+	let_assert!(Ok(clone) = file.try_clone());
+	let_assert!(Err(Error::MissingSeals(missing)) = GrowableMapping::new(clone));
+	assert!(missing == Seals::from(Seal::Shrink));
+
+	assert!(let Ok(()) = file.add_seal(Seal::Shrink));
+	let_assert!(Ok(clone) = file.try_clone());
+	let_assert!(Ok(mut mapping) = GrowableMapping::new(clone));
+	assert!(mapping.is_empty());
+	assert!(mapping.generation() == 0);
+	assert!(let Ok(false) = mapping.refresh());
+
+	use std::os::unix::fs::FileExt;
+	assert!(let Ok(()) = file.write_all_at(b"Hello", 0));
+	assert!(mapping.is_empty());
+	assert!(let Ok(true) = mapping.refresh());
+	assert!(mapping.generation() == 1);
+	let mut buffer = [0; 8];
+	assert!(mapping.read_at(&mut buffer, 0) == 5);
+	assert!(&buffer[..5] == b"Hello");
+
+	assert!(let Ok(()) = file.set_len(100_000));
+	assert!(let Ok(()) = file.write_all_at(b"world", 99_995));
+	assert!(let Ok(true) = mapping.refresh());
+	assert!(mapping.generation() == 2);
+	assert!(mapping.len() == 100_000);
+	assert!(mapping.read_at(&mut buffer, 99_995) == 5);
+	assert!(&buffer[..5] == b"world");
+	assert!(mapping.read_at(&mut buffer, 100_000) == 0);
+	assert!(let Ok(false) = mapping.refresh());
+	assert!(mapping.generation() == 2);
+
+	// Borrowing the memory as a slice requires the write seal.
+	let_assert!(Err(Error::MissingSeals(missing)) = mapping.as_slice());
+	assert!(missing == Seals::from(Seal::Write));
+	let_assert!(Ok(sealed) = MemFile::create_sealable("foo"));
+	assert!(let Ok(()) = sealed.write_all_at(b"Hello", 0));
+	assert!(let Ok(()) = sealed.add_seals(Seal::Shrink | Seal::Write));
+	let_assert!(Ok(mapping) = GrowableMapping::new(sealed));
+	assert!(let Ok(b"Hello") = mapping.as_slice());
+}
+
+#[test]
+fn mapping_advice() {
+	use memfile::{Advice, GrowableMapping};
+
+	let_assert!(Ok(file) = MemFile::create_sealable("foo"));
+	assert!(let Ok(()) = file.set_len(8 * 4096));
+	assert!(let Ok(()) = file.add_seal(Seal::Shrink));
+	let_assert!(Ok(mapping) = GrowableMapping::new(file));
+
+	assert!(let Ok(()) = mapping.advise(Advice::WillNeed));
+	assert!(let Ok(()) = mapping.advise_range(4096..8192, Advice::DontNeed));
+	let_assert!(Err(error) = mapping.advise_range(0..9 * 4096, Advice::WillNeed));
+	assert!(error.kind() == std::io::ErrorKind::InvalidInput);
+
+	// Populating for writing requires a writable mapping.
+	#[cfg(target_os = "linux")]
+	assert!(let Err(_) = mapping.advise(Advice::PopulateWrite));
+
+	// Locking may fail due to resource limits, but unlocking should always work.
+	let _ = mapping.lock();
+	assert!(let Ok(()) = mapping.unlock());
+
+	#[cfg(target_os = "linux")]
+	{
+		use memfile::MemoryPolicy;
+		// Not all kernels are built with NUMA support, so only check that local and default policies are accepted if it is.
+		match mapping.set_memory_policy(MemoryPolicy::Local, false) {
+			Ok(()) => assert!(let Ok(()) = mapping.set_memory_policy(MemoryPolicy::Default, false)),
+			Err(e) => assert!(e.raw_os_error() == Some(libc::ENOSYS)),
+		}
+	}
+}
+
+#[test]
+fn mapping_advice_empty() {
+	use memfile::{Advice, GrowableMapping};
+
+	let_assert!(Ok(file) = MemFile::create_sealable("foo"));
+	assert!(let Ok(()) = file.add_seal(Seal::Shrink));
+	let_assert!(Ok(mapping) = GrowableMapping::new(file));
+	assert!(mapping.len() == 0);
+
+	assert!(let Ok(()) = mapping.advise(Advice::WillNeed));
+	assert!(let Ok(()) = mapping.advise_range(0..0, Advice::DontNeed));
+	let_assert!(Err(error) = mapping.advise_range(0..1, Advice::WillNeed));
+	assert!(error.kind() == std::io::ErrorKind::InvalidInput);
+	assert!(let Ok(()) = mapping.lock());
+	assert!(let Ok(()) = mapping.unlock());
+	#[cfg(target_os = "linux")]
+	assert!(let Ok(()) = mapping.set_memory_policy(memfile::MemoryPolicy::Local, false));
+}
+
+#[test]
+fn transparent_huge_pages() {
+	use memfile::{CreateOptions, Error, GrowableMapping, ShmemThpSetting, ThpMode};
+
+	let_assert!(Ok(setting) = ShmemThpSetting::current());
+	let options = CreateOptions::new().allow_sealing(true).transparent_huge_pages(ThpMode::Require);
+	if setting.allows_huge_pages() {
+		let_assert!(Ok(file) = options.create("foo"));
+		assert!(file.transparent_huge_pages() == Some(ThpMode::Require));
+	} else {
+		assert!(let Err(Error::TransparentHugePagesUnavailable) = options.create("foo"));
+	}
+
+	let options = CreateOptions::new().allow_sealing(true).transparent_huge_pages(ThpMode::Enable);
+	let_assert!(Ok(file) = options.create("foo"));
+	assert!(file.transparent_huge_pages() == Some(ThpMode::Enable));
+	assert!(let Ok(()) = file.set_len(4 << 20));
+	assert!(let Ok(()) = file.add_seal(Seal::Shrink));
+	let_assert!(Ok(clone) = file.try_clone());
+	assert!(clone.transparent_huge_pages() == Some(ThpMode::Enable));
+	let_assert!(Ok(mapping) = GrowableMapping::new(clone));
+	let_assert!(Ok(bytes) = mapping.huge_page_bytes());
+	assert!(bytes <= 4 << 20);
+
+	let options = CreateOptions::new().transparent_huge_pages(ThpMode::Enable).huge_tlb(memfile::HugeTlb::Huge2MB);
+	let_assert!(Err(error) = options.create("foo"));
+	assert!(error.kind() == std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn private_mapping_and_snapshot() {
+	use std::os::unix::fs::FileExt;
+	use memfile::Error;
+
+	let_assert!(Ok(file) = MemFile::create_sealable("foo"));
+	assert!(let Ok(()) = file.write_all_at(b"Hello world!", 0));
+	assert!(let Err(Error::MissingSeals(_)) = file.map_private());
+
+	assert!(let Ok(()) = file.add_seal(Seal::Shrink));
+	let_assert!(Err(Error::MissingSeals(missing)) = file.map_private());
+	assert!(missing == Seals::from(Seal::Write));
+
+	// A sealed snapshot of the file can be mapped.
+	let_assert!(Ok(frozen) = file.snapshot("frozen", Seal::Shrink | Seal::Write));
+	let_assert!(Ok(mut private) = frozen.map_private());
+	assert!(&private[..] == b"Hello world!");
+	private[..5].copy_from_slice(b"HELLO");
+	assert!(&private[..] == b"HELLO world!");
+
+	// Changes to the private mapping are not written to the file.
+	let mut buffer = [0; 12];
+	assert!(let Ok(()) = frozen.read_exact_at(&mut buffer, 0));
+	assert!(&buffer == b"Hello world!");
+
+	// Discarding the private pages restores the contents of the file.
+	assert!(let Ok(()) = private.advise(memfile::Advice::DontNeed));
+	assert!(&private[..] == b"Hello world!");
+
+	let_assert!(Ok(mut snapshot) = file.snapshot("snapshot", Seal::Write | Seal::Shrink | Seal::Grow));
+	let_assert!(Ok(seals) = snapshot.get_seals());
+	assert!(seals == Seal::Write | Seal::Shrink | Seal::Grow);
+	let mut contents = Vec::new();
+	assert!(let Ok(12) = snapshot.read_to_end(&mut contents));
+	assert!(contents == b"Hello world!");
+
+	// The snapshot does not change when the original is modified.
+	assert!(let Ok(()) = file.write_all_at(b"Bye", 0));
+	assert!(let Ok(()) = snapshot.read_exact_at(&mut buffer, 0));
+	assert!(&buffer == b"Hello world!");
+
+	let_assert!(Ok(unsealed) = file.snapshot("unsealed", Seals::empty()));
+	let_assert!(Ok(seals) = unsealed.get_seals());
+	assert!(seals.is_empty());
+}
+
+#[test]
+fn content_hash_and_eq() {
+	use std::collections::hash_map::DefaultHasher;
+	use std::hash::Hasher;
+	use std::os::unix::fs::FileExt;
+	use memfile::{Error, FrozenDigest};
+
+	let data: Vec<u8> = (0..20_000u32).map(|i| i as u8).collect();
+	let_assert!(Ok(a) = MemFile::create_sealable("a"));
+	let_assert!(Ok(b) = MemFile::create_sealable("b"));
+	assert!(let Ok(()) = a.write_all_at(&data, 0));
+	assert!(let Ok(()) = b.write_all_at(&data, 0));
+
+	// Hashing in chunks gives the same result as hashing everything at once.
+	let mut expected = DefaultHasher::new();
+	expected.write(&data);
+	let mut hasher = DefaultHasher::new();
+	assert!(let Ok(()) = a.content_hash(&mut hasher));
+	assert!(hasher.finish() == expected.finish());
+
+	assert!(let Ok(true) = a.content_eq(&b));
+	assert!(let Ok(true) = a.content_eq(&a));
+	assert!(let Ok(()) = b.write_all_at(b"x", 19_999));
+	assert!(let Ok(false) = a.content_eq(&b));
+	assert!(let Ok(()) = b.set_len(10));
+	assert!(let Ok(false) = a.content_eq(&b));
+
+	// A frozen digest requires the file to be sealed against all modifications.
+	let_assert!(Err(Error::MissingSeals(missing)) = FrozenDigest::compute(&a, DefaultHasher::new()));
+	assert!(missing == Seal::Write | Seal::Shrink | Seal::Grow);
+	assert!(let Ok(()) = a.add_seals(Seal::Write | Seal::Shrink | Seal::Grow));
+	let_assert!(Ok(digest) = FrozenDigest::compute(&a, DefaultHasher::new()));
+	assert!(digest.len() == 20_000);
+
+	// Sealed files are read through a mapping, which must give the same result.
+	let mut hasher = DefaultHasher::new();
+	hasher.write(&data);
+	assert!(digest.digest() == hasher.finish());
+
+	let_assert!(Ok(c) = a.snapshot("c", FrozenDigest::REQUIRED_SEALS));
+	let_assert!(Ok(other) = FrozenDigest::compute(&c, DefaultHasher::new()));
+	assert!(digest == other);
+	assert!(let Ok(true) = a.content_eq(&c));
+}
+
+#[test]
+fn wayland_shm_pool() {
+	use memfile::wayland::{Format, ShmPool};
+
+	let_assert!(Ok(mut pool) = ShmPool::new("pool", 4096));
+	let_assert!(Ok(seals) = pool.file().get_seals());
+	assert!(seals.contains(Seal::Shrink));
+
+	let_assert!(Err(error) = pool.allocate(10, 10, 39, Format::Argb8888));
+	assert!(error.kind() == std::io::ErrorKind::InvalidInput);
+
+	let_assert!(Ok(a) = pool.allocate(16, 16, 64, Format::Xrgb8888));
+	assert!(a.offset() == 0);
+	assert!(a.len() == 1024);
+	assert!(pool.len() == 4096);
+
+	// The second buffer does not fit, so the pool has to grow.
+	let_assert!(Ok(b) = pool.allocate(32, 32, 128, Format::Argb8888));
+	assert!(b.offset() == 1024);
+	assert!(pool.len() == 8192);
+	let_assert!(Ok(metadata) = pool.file().metadata());
+	assert!(metadata.len() == 8192);
+
+	pool.pixels_mut(&a).fill(1);
+	pool.pixels_mut(&b).fill(2);
+	assert!(pool.pixels(&a).iter().all(|&x| x == 1));
+	assert!(pool.pixels(&b).iter().all(|&x| x == 2));
+
+	// Freed memory is reused, and stale handles are ignored.
+	pool.free(&a);
+	let_assert!(Ok(c) = pool.allocate(8, 8, 32, Format::Rgb565));
+	assert!(c.offset() == 0);
+	pool.mark_busy(&a);
+	assert!(!pool.is_busy(&a));
+	assert!(!pool.is_busy(&c));
+
+	// Buffer rings skip buffers that are busy.
+	let_assert!(Ok(mut ring) = pool.allocate_ring(3, 16, 16, 64, Format::Argb8888));
+	let [x, y, z] = ring.buffers() else { panic!("expected three buffers") };
+	let (x, y, z) = (*x, *y, *z);
+	assert!(ring.next_available(&pool) == Some(x));
+	pool.mark_busy(&x);
+	assert!(ring.next_available(&pool) == Some(y));
+	pool.mark_busy(&y);
+	pool.mark_busy(&z);
+	assert!(ring.next_available(&pool) == None);
+	pool.release(&x);
+	assert!(ring.next_available(&pool) == Some(x));
+}
+
+#[test]
+#[should_panic = "buffer does not belong to a live slot of the pool"]
+fn wayland_shm_pool_freed_buffer_pixels() {
+	use memfile::wayland::{Format, ShmPool};
+
+	let_assert!(Ok(mut pool) = ShmPool::new("pool", 4096));
+	let_assert!(Ok(buffer) = pool.allocate(16, 16, 64, Format::Xrgb8888));
+	pool.free(&buffer);
+	pool.pixels_mut(&buffer);
+}
+
+#[test]
+fn memfile_arena() {
+	use memfile::{Error, MemFileArena, ShmPtr};
+	use std::os::unix::fs::FileExt;
+
+	let_assert!(Ok(arena) = MemFileArena::create("arena", 4096));
+	assert!(arena.capacity() == 4096);
+	assert!(arena.used() == 0);
+	let_assert!(Ok(a) = arena.alloc(0xDEAD_BEEF_u32));
+	let_assert!(Ok(b) = arena.alloc(42_u64));
+	assert!(a.offset() == 64);
+	assert!(b.offset() == 72);
+	assert!(arena.used() == 16);
+
+	// A second mapping of the same file resolves the handles to the same values at a different address.
+	let_assert!(Ok(file) = arena.file().try_clone());
+	let_assert!(Ok(other) = MemFileArena::attach(file));
+	let_assert!(Some(ptr) = other.resolve(b));
+	assert!(unsafe { *ptr.as_ptr() } == 42);
+	let_assert!(Some(ptr) = other.resolve(a));
+	assert!(unsafe { *ptr.as_ptr() } == 0xDEAD_BEEF);
+	assert!(other.to_shm_ptr(ptr.as_ptr()) == Some(a));
+	let_assert!(Some(local) = arena.resolve(a));
+	assert!(local != ptr);
+
+	// Allocations from either mapping share the same state.
+	let_assert!(Ok(c) = other.alloc([1_u8; 8]));
+	assert!(c.offset() == 80);
+	assert!(arena.used() == 24);
+
+	// Handles outside the allocated region or with the wrong alignment do not resolve.
+	assert!(arena.resolve(ShmPtr::<u64>::from_offset(80)).is_some());
+	assert!(arena.resolve(ShmPtr::<u64>::from_offset(84)).is_none());
+	assert!(arena.resolve(ShmPtr::<u64>::from_offset(88)).is_none());
+	assert!(arena.resolve(ShmPtr::<u8>::from_offset(0)).is_none());
+
+	// Only the last allocation can be freed.
+	let layout = std::alloc::Layout::new::<u64>();
+	let_assert!(Ok(d) = arena.allocate_layout(layout));
+	let_assert!(Ok(e) = arena.allocate_layout(layout));
+	unsafe { arena.deallocate_layout(d, layout) };
+	assert!(arena.used() == 40);
+	unsafe { arena.deallocate_layout(e, layout) };
+	assert!(arena.used() == 32);
+
+	let_assert!(Err(error) = arena.alloc([0_u8; 4096]));
+	assert!(error.kind() == std::io::ErrorKind::OutOfMemory);
+
+	// Attaching requires the seals and the header.
+	let_assert!(Ok(file) = MemFile::create_sealable("not-an-arena"));
+	assert!(let Ok(()) = file.set_len(4096));
+	let_assert!(Ok(clone) = file.try_clone());
+	let_assert!(Err(Error::MissingSeals(_)) = MemFileArena::attach(clone));
+	assert!(let Ok(()) = file.add_seals(Seal::Shrink | Seal::Grow));
+	let_assert!(Err(error) = MemFileArena::attach(file));
+	assert!(std::io::Error::from(error).kind() == std::io::ErrorKind::InvalidData);
+
+	// An allocation pointer outside of the file is rejected when attaching.
+	let_assert!(Ok(file) = arena.file().try_clone());
+	assert!(let Ok(()) = file.write_all_at(&u64::MAX.to_ne_bytes(), 8));
+	let_assert!(Err(error) = MemFileArena::attach(file));
+	assert!(std::io::Error::from(error).kind() == std::io::ErrorKind::InvalidData);
+
+	// If the header is corrupted after attaching, handles still can not point outside of the mapping.
+	assert!(arena.used() == 4096 - 64);
+	assert!(arena.resolve(ShmPtr::<u64>::from_offset(4088)).is_some());
+	assert!(arena.resolve(ShmPtr::<u64>::from_offset(4096)).is_none());
+	assert!(let Ok(()) = arena.file().write_all_at(&0_u64.to_ne_bytes(), 8));
+	assert!(arena.used() == 0);
+	assert!(arena.resolve(a).is_none());
+	let_assert!(Err(error) = arena.alloc(0_u8));
+	assert!(error.kind() == std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn relocatable_collections() {
+	use memfile::MemFileArena;
+	use memfile::relocatable::{RelPtr, Relocatable, ShmHashMap, ShmSlice, ShmVec, Validator};
+
+	#[repr(C)]
+	struct Root {
+		names: ShmVec<ShmSlice<u8>>,
+		index: ShmHashMap<u32, u64>,
+		first: RelPtr<ShmSlice<u8>>,
+	}
+
+	unsafe impl Relocatable for Root {
+		fn validate(&self, validator: &mut Validator) -> std::io::Result<()> {
+			self.names.validate(validator)?;
+			self.index.validate(validator)?;
+			self.first.validate(validator)
+		}
+	}
+
+	#[repr(C)]
+	struct Node {
+		next: RelPtr<Node>,
+	}
+
+	unsafe impl Relocatable for Node {
+		fn validate(&self, validator: &mut Validator) -> std::io::Result<()> {
+			self.next.validate(validator)
+		}
+	}
+
+	let_assert!(Ok(arena) = MemFileArena::create("relocatable", 64 * 1024));
+	let_assert!(Ok(root_ptr) = arena.alloc(Root { names: ShmVec::new(), index: ShmHashMap::new(), first: RelPtr::null() }));
+	let_assert!(Some(mut root) = arena.resolve(root_ptr));
+	let root = unsafe { root.as_mut() };
+
+	assert!(let Ok(()) = root.index.allocate(&arena, 4));
+	for (i, name) in ["zero", "one", "two"].iter().enumerate() {
+		assert!(let Ok(()) = root.names.push(&arena, ShmSlice::new()));
+		let_assert!(Ok(names) = unsafe { root.names.as_mut_slice(&arena) });
+		assert!(let Ok(()) = names[i].allocate_copy(&arena, name.as_bytes()));
+		assert!(let Ok(None) = root.index.insert(&arena, name.len() as u32 * 10 + i as u32, i as u64));
+	}
+	let_assert!(Ok(names) = root.names.as_slice(&arena));
+	unsafe { root.first.set(&names[0]) };
+
+	assert!(root.index.len() == 3);
+	assert!(let Ok(Some(1)) = root.index.insert(&arena, 31, 100));
+	assert!(let Ok(Some(100)) = root.index.remove(&arena, &31));
+	assert!(let Ok(None) = root.index.get(&arena, &31));
+	assert!(let Ok(None) = root.index.insert(&arena, 7, 7));
+	assert!(let Ok(None) = root.index.insert(&arena, 8, 8));
+	let_assert!(Err(error) = root.index.insert(&arena, 9, 9));
+	assert!(error.kind() == std::io::ErrorKind::OutOfMemory);
+
+	// Another mapping of the arena at a different address sees the same data after validation.
+	let_assert!(Ok(file) = arena.file().try_clone());
+	let_assert!(Ok((other, copy)) = MemFileArena::attach_validated(file, root_ptr));
+	let copy = unsafe { copy.as_ref() };
+	let_assert!(Ok(names) = copy.names.as_slice(&other));
+	let names: Vec<_> = names.iter().map(|name| name.as_slice(&other).unwrap().to_vec()).collect();
+	assert!(names == [b"zero".to_vec(), b"one".to_vec(), b"two".to_vec()]);
+	let_assert!(Ok(Some(first)) = copy.first.get(&other));
+	assert!(let Ok(b"zero") = first.as_slice(&other));
+	assert!(let Ok(Some(&2)) = copy.index.get(&other, &32));
+	assert!(let Ok(true) = copy.index.contains_key(&other, &40));
+	let_assert!(Ok(entries) = copy.index.iter(&other));
+	let mut entries: Vec<_> = entries.map(|(&key, &value)| (key, value)).collect();
+	entries.sort();
+	assert!(entries == [(7, 7), (8, 8), (32, 2), (40, 0)]);
+
+	// Corrupted offsets are detected by validation and by every access.
+	let_assert!(Ok(vec) = arena.alloc(ShmVec::<u64>::new()));
+	let_assert!(Some(mut vec_ref) = arena.resolve(vec));
+	assert!(let Ok(()) = unsafe { vec_ref.as_mut() }.push(&arena, 1));
+	let_assert!(Some(len) = arena.resolve(memfile::ShmPtr::<u64>::from_offset(vec.offset() + 8)));
+	unsafe { len.as_ptr().write(1_000_000) };
+	let_assert!(Err(error) = other.validate(vec));
+	assert!(error.kind() == std::io::ErrorKind::InvalidData);
+	let_assert!(Err(error) = unsafe { vec_ref.as_ref() }.as_slice(&arena));
+	assert!(error.kind() == std::io::ErrorKind::InvalidData);
+
+	// Cycles are detected by validation instead of being followed forever.
+	let_assert!(Ok(a) = arena.alloc(Node { next: RelPtr::null() }));
+	let_assert!(Ok(b) = arena.alloc(Node { next: RelPtr::null() }));
+	let_assert!(Some(mut a_ref) = arena.resolve(a));
+	let_assert!(Some(mut b_ref) = arena.resolve(b));
+	unsafe { a_ref.as_mut().next.set(b_ref.as_ptr()) };
+	unsafe { b_ref.as_mut().next.set(a_ref.as_ptr()) };
+	let_assert!(Err(error) = other.validate(a));
+	assert!(error.kind() == std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn fallible_mapping() {
+	use memfile::FallibleMapping;
+
+	let_assert!(Ok(file) = MemFile::create_default("fallible"));
+	assert!(file.may_shrink());
+	let data: Vec<u8> = (0..3 * 4096).map(|i| i as u8).collect();
+	assert!(let Ok(()) = std::os::unix::fs::FileExt::write_all_at(&file, &data, 0));
+	let_assert!(Ok(peer) = file.try_clone());
+	let_assert!(Ok(mut mapping) = FallibleMapping::new(file));
+	assert!(mapping.len() == 3 * 4096);
+
+	let mut buffer = [0; 16];
+	assert!(let Ok(()) = mapping.read_exact_at(&mut buffer, 4090));
+	assert!(buffer[..] == data[4090..4106]);
+
+	// Reading pages that were removed by shrinking the file gives an error instead of a signal.
+	assert!(let Ok(()) = peer.set_len(4096));
+	assert!(let Ok(0) = mapping.read_at(&mut buffer, 8192));
+	let_assert!(Err(error) = mapping.read_exact_at(&mut buffer, 4090));
+	assert!(error.kind() == std::io::ErrorKind::UnexpectedEof);
+	assert!(let Ok(6) = mapping.read_at(&mut buffer, 4090));
+	assert!(buffer[..6] == data[4090..4096]);
+
+	assert!(let Ok(true) = mapping.refresh());
+	assert!(mapping.len() == 4096);
+	assert!(let Ok(0) = mapping.read_at(&mut buffer, 4096));
+
+	let_assert!(Ok(sealed) = MemFile::create_sealable("sealed"));
+	assert!(let Ok(()) = sealed.add_seals(Seal::Shrink.into()));
+	assert!(!sealed.may_shrink());
+}
+
+#[test]
+fn memory_usage() {
+	let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+	let page = vec![1; page_size as usize];
+	let_assert!(Ok(mut file) = MemFile::create_default("usage"));
+	assert!(let Ok(()) = file.set_len(16 * page_size));
+	let_assert!(Ok(usage) = file.usage());
+	assert!(usage.len() == 16 * page_size);
+	assert!(usage.allocated_bytes() == 0);
+	assert!(usage.data_bytes() == Some(0));
+	assert!(usage.resident_pages() == Some(0));
+
+	// Write two separate pages, and check that the shared file position is never touched.
+	assert!(let Ok(()) = std::os::unix::fs::FileExt::write_all_at(&file, &page, 2 * page_size));
+	assert!(let Ok(()) = std::os::unix::fs::FileExt::write_all_at(&file, &page, 8 * page_size));
+	assert!(let Ok(5) = file.seek(std::io::SeekFrom::Start(5)));
+	let_assert!(Ok(mut clone) = file.try_clone());
+	let_assert!(Ok(usage) = clone.usage());
+	assert!(usage.allocated_bytes() == 2 * page_size);
+	assert!(usage.data_bytes() == Some(2 * page_size));
+	assert!(let Ok(5) = file.stream_position());
+	assert!(let Ok(5) = clone.stream_position());
+
+	// Touching a page through a mapping makes it resident in this process.
+	// The kernel may also map the other page with data while handling the page fault.
+	let data = unsafe {
+		libc::mmap(std::ptr::null_mut(), 16 * page_size as usize, libc::PROT_READ, libc::MAP_SHARED, file.as_raw_fd(), 0)
+	};
+	assert!(data != libc::MAP_FAILED);
+	assert!(unsafe { data.cast::<u8>().add(2 * page_size as usize).read_volatile() } == 1);
+	let_assert!(Ok(usage) = file.usage());
+	let_assert!(Some(resident) = usage.resident_pages());
+	assert!((1..=2).contains(&resident));
+	assert!(usage.swapped_pages() == Some(0));
+	unsafe { libc::munmap(data, 16 * page_size as usize) };
+}
+
+#[test]
+fn max_size() {
+	use memfile::{CreateOptions, Error};
+	use std::os::unix::fs::FileExt;
+
+	let_assert!(Ok(mut file) = MemFile::create("limited", CreateOptions::new().allow_sealing(true).max_size(100)));
+	assert!(file.max_size() == Some(100));
+	let_assert!(Err(Error::SizeLimitExceeded(100)) = file.set_len(101));
+	assert!(let Ok(()) = file.set_len(50));
+
+	// Writes that cross the limit are shortened, and writes beyond the limit fail.
+	assert!(let Ok(10) = file.write_at(&[1; 10], 20));
+	assert!(let Ok(90) = file.seek(std::io::SeekFrom::Start(90)));
+	assert!(let Ok(10) = file.write(&[2; 20]));
+	let_assert!(Err(error) = file.write(&[2; 20]));
+	assert!(error.kind() == std::io::ErrorKind::FileTooLarge);
+	let_assert!(Ok(metadata) = file.metadata());
+	assert!(metadata.len() == 100);
+
+	// Once the limit is reached, the file is sealed against growing.
+	let_assert!(Ok(seals) = file.get_seals());
+	assert!(seals.contains(Seal::Grow));
+
+	// Clones share the limit, and vectored writes and copies are limited too.
+	let_assert!(Ok(other) = MemFile::create("other", CreateOptions::new().max_size(8)));
+	let_assert!(Ok(clone) = other.try_clone());
+	assert!(clone.max_size() == Some(8));
+	let bufs = [std::io::IoSlice::new(b"abcd"), std::io::IoSlice::new(b"efgh"), std::io::IoSlice::new(b"ijkl")];
+	assert!(let Ok(6) = clone.write_vectored_at(&bufs, 2));
+	let_assert!(Ok(source) = MemFile::create_default("source"));
+	assert!(let Ok(()) = source.write_all_at(b"Hello world!", 0));
+	assert!(let Ok(8) = other.copy_from_fd(&source, 0..u64::MAX));
+	let mut buffer = [0; 8];
+	assert!(let Ok(()) = other.read_exact_at(&mut buffer, 0));
+	assert!(&buffer == b"Hello wo");
+
+	let_assert!(Ok(unlimited) = MemFile::create_default("unlimited"));
+	assert!(unlimited.max_size() == None);
+}
@@ -0,0 +1,82 @@
+use assert2::{assert, let_assert};
+use memfile::registry::{Client, Registry};
+use memfile::{MemFile, Seal};
+use std::io::Write;
+use std::os::unix::fs::FileExt;
+use std::os::unix::net::SocketAddr;
+
+#[track_caller]
+fn start_registry(name: &str) -> SocketAddr {
+	let path = std::env::temp_dir().join(format!("memfile-test-{}-{name}.sock", std::process::id()));
+	let _ = std::fs::remove_file(&path);
+	let_assert!(Ok(addr) = SocketAddr::from_pathname(&path));
+	let_assert!(Ok(registry) = Registry::bind(&addr));
+	let registry = registry.required_seals(Seal::Shrink);
+	std::thread::spawn(move || registry.serve());
+	addr
+}
+
+#[test]
+fn publish_and_lookup() {
+	let addr = start_registry("publish-and-lookup");
+
+	let_assert!(Ok(mut file) = MemFile::create_sealable("foo"));
+	assert!(let Ok(()) = file.write_all(b"Hello world!"));
+
+	// The registry requires the shrink seal, and we require the write seal.
+	let_assert!(Ok(mut publisher) = Client::connect(&addr));
+	let_assert!(Err(error) = publisher.publish("foo", &file, Seal::Write));
+	assert!(error.kind() == std::io::ErrorKind::PermissionDenied);
+	assert!(let Ok(()) = file.add_seal(Seal::Shrink));
+	let_assert!(Err(error) = publisher.publish("foo", &file, Seal::Write));
+	assert!(error.kind() == std::io::ErrorKind::PermissionDenied);
+	assert!(let Ok(()) = file.add_seal(Seal::Write));
+	assert!(let Ok(()) = publisher.publish("foo", &file, Seal::Write));
+
+	let_assert!(Err(error) = publisher.publish("foo", &file, Seal::Write));
+	assert!(error.kind() == std::io::ErrorKind::AlreadyExists);
+
+	let_assert!(Ok(mut consumer) = Client::connect(&addr));
+	let_assert!(Err(error) = consumer.lookup("bar"));
+	assert!(error.kind() == std::io::ErrorKind::NotFound);
+	// The received file shares the file position with the published file, so use positional reads.
+	let_assert!(Ok(received) = consumer.lookup("foo"));
+	let mut buffer = [0; 12];
+	assert!(let Ok(()) = received.read_exact_at(&mut buffer, 0));
+	assert!(&buffer == b"Hello world!");
+	let_assert!(Ok(seals) = received.get_seals());
+	assert!(seals == Seal::Shrink | Seal::Write);
+}
+
+#[test]
+fn publish_from_other_process() {
+	let addr = start_registry("other-process");
+	let_assert!(Some(path) = addr.as_pathname());
+	let_assert!(Ok(exe) = std::env::current_exe());
+	let_assert!(Ok(status) = std::process::Command::new(exe)
+		.args(["--exact", "child_publisher", "--nocapture"])
+		.env("MEMFILE_TEST_REGISTRY", path)
+		.status()
+	);
+	assert!(status.success());
+
+	let_assert!(Ok(mut client) = Client::connect(&addr));
+	let_assert!(Ok(received) = client.lookup("from-child"));
+	let mut buffer = [0; 29];
+	assert!(let Ok(()) = received.read_exact_at(&mut buffer, 0));
+	assert!(&buffer == b"Hello from the child process!");
+}
+
+/// Helper for `publish_from_other_process`, which does nothing unless run as child process.
+#[test]
+fn child_publisher() {
+	let Some(path) = std::env::var_os("MEMFILE_TEST_REGISTRY") else {
+		return;
+	};
+	let_assert!(Ok(addr) = SocketAddr::from_pathname(path));
+	let_assert!(Ok(mut file) = MemFile::create_sealable("from-child"));
+	assert!(let Ok(()) = file.write_all(b"Hello from the child process!"));
+	assert!(let Ok(()) = file.add_seal(Seal::Shrink));
+	let_assert!(Ok(mut client) = Client::connect(&addr));
+	assert!(let Ok(()) = client.publish("from-child", &file, Seal::Shrink));
+}